@@ -0,0 +1,325 @@
+// The napi bindings leave symbols that only Node.js provides, so a binary
+// built with them can't link.
+#[cfg(feature = "napi")]
+compile_error!(
+    "the `froxscript` command line can't be built with the napi bindings, \
+     build it with `cargo build --no-default-features --features cli`"
+);
+
+use froxcript::rog::*;
+use froxcript::*;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Uso: froxscript [OPÇÕES] [SCRIPT]...
+
+Roda o script passado como argumento, lido de um arquivo ou da entrada padrão.
+Sem script, abre o modo interativo quando a entrada é um terminal.
+
+Opções:
+  -f, --file <ARQUIVO>       lê o script de um arquivo (`-` para a entrada padrão)
+  -a, --attributes <ARQUIVO> carrega os atributos de um arquivo JSON ou TOML
+  -w, --write                salva os atributos atualizados de volta no arquivo
+  -s, --seed <HEX>           rola os dados com essa semente
+  -l, --locale <IDIOMA>      idioma das mensagens de erro (`pt-BR` ou `en`)
+  -i, --interactive          abre o modo interativo
+  -h, --help                 mostra esta mensagem
+
+No modo interativo, cada linha é rodada assim que enviada e os atributos e
+variáveis continuam valendo nas linhas seguintes. Comandos:
+  :atributos   mostra os atributos e variáveis
+  :sair        sai (assim como Ctrl-D)
+";
+
+#[derive(Default)]
+struct Args {
+    script: Vec<String>,
+    file: Option<String>,
+    attributes: Option<PathBuf>,
+    write: bool,
+    seed: Option<Seed>,
+    locale: Locale,
+    interactive: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("faltou o valor de `{}`", name))
+        };
+        match arg.as_str() {
+            "-f" | "--file" => parsed.file = Some(value(&arg)?),
+            "-a" | "--attributes" => parsed.attributes = Some(value(&arg)?.into()),
+            "-w" | "--write" => parsed.write = true,
+            "-s" | "--seed" => {
+                parsed.seed = Some(Seed::from_hex(&value(&arg)?).map_err(|e| e.to_string())?)
+            }
+            "-l" | "--locale" => {
+                let tag = value(&arg)?;
+                parsed.locale =
+                    Locale::from_tag(&tag).ok_or_else(|| format!("idioma `{}` não existe", tag))?
+            }
+            "-i" | "--interactive" => parsed.interactive = true,
+            "-h" | "--help" => return Ok(None),
+            "--" => parsed.script.extend(args.by_ref()),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("opção `{}` não existe", arg))
+            }
+            _ => parsed.script.push(arg),
+        }
+    }
+    if parsed.write && parsed.attributes.is_none() {
+        return Err("`--write` precisa de `--attributes`".to_string());
+    }
+    Ok(Some(parsed))
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+/// An attribute as written in the attributes file.
+enum Attribute {
+    Value(f64),
+    /// The faces of a face set, kept in the attribute map as `NAME.1`,
+    /// `NAME.2` and so on.
+    Faces(Vec<f64>),
+}
+
+fn load_attributes(path: &Path) -> Result<HashMap<String, f64>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let attributes: Vec<(String, Option<Attribute>)> = if is_toml(path) {
+        let table: toml::value::Table =
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let number = |value: &toml::Value| match value {
+            toml::Value::Integer(value) => Some(*value as f64),
+            toml::Value::Float(value) => Some(*value),
+            _ => None,
+        };
+        table
+            .into_iter()
+            .map(|(name, value)| match &value {
+                toml::Value::Array(faces) => {
+                    let faces: Option<_> = faces.iter().map(number).collect();
+                    (name, faces.map(Attribute::Faces))
+                }
+                _ => (name, number(&value).map(Attribute::Value)),
+            })
+            .collect()
+    } else {
+        let object: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        object
+            .into_iter()
+            .map(|(name, value)| match &value {
+                serde_json::Value::Array(faces) => {
+                    let faces: Option<_> = faces.iter().map(serde_json::Value::as_f64).collect();
+                    (name, faces.map(Attribute::Faces))
+                }
+                _ => (name, value.as_f64().map(Attribute::Value)),
+            })
+            .collect()
+    };
+
+    let mut attr_map = HashMap::new();
+    for (name, attribute) in attributes {
+        match attribute {
+            Some(Attribute::Value(value)) => {
+                attr_map.insert(name, value);
+            }
+            Some(Attribute::Faces(faces)) => {
+                for (i, face) in faces.into_iter().enumerate() {
+                    attr_map.insert(format!("{}.{}", name, i + 1), face);
+                }
+            }
+            None => {
+                return Err(format!(
+                    "{}: `{}` precisa ser um número ou uma lista de números",
+                    path.display(),
+                    name
+                ))
+            }
+        }
+    }
+    Ok(attr_map)
+}
+
+/// Writes the attributes back, sorted, leaving `$` variables out since they
+/// only last until the end of the script. Whole numbers are written as
+/// integers, and face sets as lists again.
+fn save_attributes(path: &Path, attr_map: &HashMap<String, f64>) -> Result<(), String> {
+    let mut attributes = BTreeMap::new();
+    let mut face_sets: BTreeMap<String, BTreeMap<usize, f64>> = BTreeMap::new();
+    for (name, value) in attr_map.iter().filter(|(name, _)| !name.starts_with('$')) {
+        let face = name
+            .rsplit_once('.')
+            .and_then(|(set, i)| Some((set, i.parse::<usize>().ok()?)));
+        match face {
+            Some((set, i)) => {
+                face_sets
+                    .entry(set.to_string())
+                    .or_default()
+                    .insert(i, *value);
+            }
+            None => {
+                attributes.insert(name.clone(), Attribute::Value(*value));
+            }
+        }
+    }
+    for (set, faces) in face_sets {
+        attributes.insert(set, Attribute::Faces(faces.into_values().collect()));
+    }
+
+    let is_integer = |value: f64| value.fract() == 0. && value.abs() < 1e15;
+    let text = if is_toml(path) {
+        let number = |value: f64| match is_integer(value) {
+            true => toml::Value::Integer(value as i64),
+            false => toml::Value::Float(value),
+        };
+        let table: toml::value::Table = attributes
+            .into_iter()
+            .map(|(name, attribute)| match attribute {
+                Attribute::Value(value) => (name, number(value)),
+                Attribute::Faces(faces) => (
+                    name,
+                    toml::Value::Array(faces.into_iter().map(number).collect()),
+                ),
+            })
+            .collect();
+        toml::to_string(&table).map_err(|e| e.to_string())?
+    } else {
+        let number = |value: f64| match is_integer(value) {
+            true => (value as i64).into(),
+            false => value.into(),
+        };
+        let object: serde_json::Map<String, serde_json::Value> = attributes
+            .into_iter()
+            .map(|(name, attribute)| match attribute {
+                Attribute::Value(value) => (name, number(value)),
+                Attribute::Faces(faces) => (name, faces.into_iter().map(number).collect()),
+            })
+            .collect();
+        serde_json::to_string_pretty(&object).map_err(|e| e.to_string())? + "\n"
+    };
+    std::fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Runs `input` and prints its results. The attributes are only updated when
+/// every line succeeds.
+fn run_input(
+    input: &str,
+    attr_map: &mut HashMap<String, f64>,
+    roller: &mut Roller,
+    locale: Locale,
+) -> bool {
+    let mut updated = attr_map.clone();
+    match compile(input).and_then(|script| eval(&script, &mut updated, roller)) {
+        Ok(cons) => {
+            for c in cons {
+                println!("{}", c.text);
+            }
+            *attr_map = updated;
+            true
+        }
+        Err(err) => {
+            eprintln!("{}", Diagnostic::new(&err, input, locale).render());
+            false
+        }
+    }
+}
+
+fn repl(attr_map: &mut HashMap<String, f64>, roller: &mut Roller, locale: Locale) {
+    let stdin = std::io::stdin();
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        match line.trim() {
+            "" => {}
+            ":sair" | ":quit" | ":q" => break,
+            ":atributos" | ":attributes" => {
+                for (name, value) in attr_map.iter().collect::<BTreeMap<_, _>>() {
+                    println!("{} = {}", name, value);
+                }
+            }
+            input => {
+                run_input(input, attr_map, roller, locale);
+            }
+        }
+    }
+    println!();
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .map(|_| input)
+        .map_err(|e| e.to_string())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("froxscript: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut attr_map = match args.attributes.as_deref().map(load_attributes) {
+        Some(Ok(attr_map)) => attr_map,
+        Some(Err(err)) => {
+            eprintln!("froxscript: {}", err);
+            return ExitCode::FAILURE;
+        }
+        None => HashMap::new(),
+    };
+    let mut roller = Roller::new(args.seed.unwrap_or_else(Seed::random));
+
+    let interactive = args.interactive
+        || (args.script.is_empty() && args.file.is_none() && std::io::stdin().is_terminal());
+    let ok = if interactive {
+        repl(&mut attr_map, &mut roller, args.locale);
+        true
+    } else {
+        let input = match args.file.as_deref() {
+            Some("-") => read_stdin(),
+            Some(file) => std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e)),
+            None if args.script.is_empty() => read_stdin(),
+            None => Ok(args.script.join(" ")),
+        };
+        match input {
+            Ok(input) => run_input(&input, &mut attr_map, &mut roller, args.locale),
+            Err(err) => {
+                eprintln!("froxscript: {}", err);
+                false
+            }
+        }
+    };
+
+    if ok && args.write {
+        if let Err(err) = save_attributes(args.attributes.as_deref().unwrap(), &attr_map) {
+            eprintln!("froxscript: {}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
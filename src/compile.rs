@@ -0,0 +1,545 @@
+use crate::rog::*;
+use pest::{
+    error::{ErrorVariant, InputLocation},
+    iterators::{Pair, Pairs},
+    pratt_parser::*,
+    Parser,
+};
+use pest_derive::Parser;
+use std::sync::OnceLock;
+
+#[derive(Parser)]
+#[grammar = "rog.pest"]
+struct RogParser;
+
+/// Parses a script into its `Script` tree, without rolling anything.
+pub fn compile(input: &str) -> Result<Script, RogError> {
+    static PRATT: OnceLock<PrattParser<Rule>> = OnceLock::new();
+    let pratt = PRATT.get_or_init(get_parser_logic);
+
+    let mut lines = vec![];
+    let mut offset = 0;
+    for (line, input) in input.split('\n').enumerate() {
+        let line_offset = offset + input.len() - input.trim_start().len();
+        offset += input.len() + 1;
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+        RogParser::parse(Rule::repeat, input)
+            .map_err(RogError::from)
+            .and_then(|pairs| compile_repeat(pairs, pratt, line + 1, line_offset))
+            .map(|line| lines.push(line))
+            .map_err(|e| e.locate(line + 1, line_offset))?;
+    }
+
+    Ok(Script { lines })
+}
+
+fn compile_repeat(
+    pairs: Pairs<Rule>,
+    pratt: &PrattParser<Rule>,
+    number: usize,
+    offset: usize,
+) -> Result<Line, RogError> {
+    let it = pairs
+        .into_iter()
+        .next()
+        .ok_or(RogErr::UnknownError)?
+        .into_inner();
+
+    let mut repeat_count: usize = 1;
+    let mut comment = None;
+    let mut assignment = None;
+    for pair in it {
+        match pair.as_rule() {
+            Rule::repeat_literal => {
+                let input = pair.as_str().trim();
+                repeat_count = input
+                    .strip_suffix('#')
+                    .unwrap_or(input)
+                    .parse()
+                    .map_err(|_| RogErr::UnknownError)?;
+            }
+            Rule::comment => {
+                comment = pair
+                    .as_str()
+                    .strip_prefix(';')
+                    .map(|c| c.trim().to_string())
+            }
+            Rule::assignment => assignment = Some(pair.into_inner()),
+            Rule::EOI => continue,
+            _ => unreachable!(),
+        }
+    }
+
+    let mut it = assignment.ok_or(RogErr::UnknownError)?.rev();
+    let expression_pairs = it.next().ok_or(RogErr::UnknownError)?.into_inner();
+    let expression = compile_expression(expression_pairs, pratt)?;
+
+    let assignment = match it.next() {
+        Some(assigner) => {
+            let attribute = it.next().ok_or(RogErr::UnknownError)?;
+            let op = match assigner.as_rule() {
+                Rule::assign_eq => AssignOp::Eq,
+                Rule::assign_add => AssignOp::Add,
+                Rule::assign_sub => AssignOp::Sub,
+                Rule::assign_mul => AssignOp::Mul,
+                Rule::assign_div => AssignOp::Div,
+                _ => unreachable!(),
+            };
+            Some(Assignment {
+                target: attribute.as_str().trim().to_string(),
+                op,
+                span: span_of(&attribute),
+            })
+        }
+        None => None,
+    };
+
+    Ok(Line {
+        number,
+        offset,
+        repeat: repeat_count,
+        assignment,
+        expression,
+        comment,
+    })
+}
+
+fn compile_expression(pairs: Pairs<Rule>, pratt: &PrattParser<Rule>) -> Result<Expr, RogError> {
+    pratt
+        .map_primary(|primary| compile_primary(primary, pratt))
+        .map_infix(|lhs, op, rhs| {
+            let op = match op.as_rule() {
+                Rule::and => InfixOp::And,
+                Rule::or => InfixOp::Or,
+                Rule::less_eq => InfixOp::LessEq,
+                Rule::less => InfixOp::Less,
+                Rule::greater_eq => InfixOp::GreaterEq,
+                Rule::greater => InfixOp::Greater,
+                Rule::eq => InfixOp::Eq,
+                Rule::hyper_add => InfixOp::HyperAdd,
+                Rule::hyper_sub => InfixOp::HyperSub,
+                Rule::counter_less => InfixOp::CounterLess,
+                Rule::counter_greater => InfixOp::CounterGreater,
+                Rule::add => InfixOp::Add,
+                Rule::sub => InfixOp::Sub,
+                Rule::mul => InfixOp::Mul,
+                Rule::div => InfixOp::Div,
+                _ => return Err(RogErr::UnknownError.into()),
+            };
+            Ok(Expr::Infix(op, Box::new(lhs?), Box::new(rhs?)))
+        })
+        .map_prefix(|op, rhs| {
+            let op = match op.as_rule() {
+                Rule::neg => PrefixOp::Neg,
+                Rule::not => PrefixOp::Not,
+                Rule::ceil => PrefixOp::Ceil,
+                Rule::round => PrefixOp::Round,
+                Rule::floor => PrefixOp::Floor,
+                _ => return Err(RogErr::UnknownError.into()),
+            };
+            Ok(Expr::Prefix(op, Box::new(rhs?)))
+        })
+        .map_postfix(|lhs, op| {
+            let op = match op.as_rule() {
+                Rule::percent => PostfixOp::Percent,
+                _ => return Err(RogErr::UnknownError.into()),
+            };
+            Ok(Expr::Postfix(op, Box::new(lhs?)))
+        })
+        .parse(pairs)
+}
+
+fn compile_primary(primary: Pair<Rule>, pratt: &PrattParser<Rule>) -> Result<Expr, RogError> {
+    match primary.as_rule() {
+        Rule::integer | Rule::frac | Rule::attribute => Ok(Expr::Number(compile_number(&primary))),
+        Rule::expression => compile_expression(primary.into_inner(), pratt),
+        Rule::group => Ok(Expr::Group(Box::new(compile_expression(
+            primary.into_inner(),
+            pratt,
+        )?))),
+        Rule::fate_dice => compile_fate(primary),
+        Rule::percentile_dice => compile_percentile(primary),
+        Rule::cards => compile_cards(primary),
+        Rule::dice => compile_dice(primary),
+        _ => Err(RogErr::UnknownError.into()),
+    }
+}
+
+fn compile_number(pair: &Pair<Rule>) -> Number {
+    let text = pair.as_str();
+    let attr_start = text.find(|c| matches!(c, 'A'..='Z' | '$'));
+    let (value, attribute) = if let Some(split) = attr_start {
+        let (num, attr) = text.split_at(split);
+        let num = if num.is_empty() { 1. } else { parse_float(num) };
+        (num, Some(attr.to_string()))
+    } else {
+        (parse_float(text), None)
+    };
+    Number {
+        value,
+        attribute,
+        span: span_of(pair),
+    }
+}
+
+fn parse_float(float: &str) -> f64 {
+    float
+        .parse()
+        .unwrap_or_else(|_| panic!("Expected float, got {}", float))
+}
+
+fn span_of(pair: &Pair<Rule>) -> Span {
+    let span = pair.as_span();
+    (span.start(), span.end())
+}
+
+fn compile_dice(primary: Pair<Rule>) -> Result<Expr, RogError> {
+    let span = span_of(&primary);
+    let (mut count, mut faces, mut options) = (None, None, None);
+    for pair in primary.into_inner() {
+        match pair.as_rule() {
+            Rule::dice_n => {
+                if !pair.as_str().is_empty() {
+                    count = Some(compile_number(&pair))
+                }
+            }
+            Rule::dice_side => faces = Some(compile_faces(pair)?),
+            Rule::roll_config => options = Some(compile_options(pair, count.as_ref())?),
+            _ => unreachable!(),
+        }
+    }
+    let (config, explode_span, reroll_span) =
+        options.unwrap_or_else(|| (RollConfig::new(), None, None));
+    Ok(Expr::Dice(Box::new(DiceTerm {
+        count,
+        faces: faces.ok_or(RogErr::UnknownError)?,
+        config,
+        span,
+        explode_span,
+        reroll_span,
+    })))
+}
+
+/// The options of a roll, along with where its explosion and reroll
+/// options were written.
+type RollOptions = (RollConfig<Number>, Option<Span>, Option<Span>);
+
+fn compile_options(pair: Pair<Rule>, count: Option<&Number>) -> Result<RollOptions, RogError> {
+    // `d20>=10` was a test before success counts, refuse it rather than
+    // quietly count. Pools of one die need their count from an attribute,
+    // or a failure option.
+    let single = count.is_none_or(|count| count.attribute.is_none() && count.value == 1.);
+    let bare_success = pair.clone().into_inner().find(|p| {
+        p.as_rule() == Rule::success && p.clone().into_inner().all(|p| p.as_rule() != Rule::failure)
+    });
+    if let Some(success) = bare_success.filter(|_| single) {
+        let (start, end) = span_of(&success);
+        return Err(RogError::new(RogErr::PoolSingle).at(start, end));
+    }
+    let spans_of = |rule| {
+        let pairs = pair.clone().into_inner();
+        pairs
+            .filter(move |p| p.as_rule() == rule)
+            .map(|p| span_of(&p))
+    };
+    // There is at most one explosion, but every reroll is checked.
+    let explode_span = spans_of(Rule::explode).next();
+    let reroll_span = spans_of(Rule::reroll).next();
+    let config = compile_roll_config(pair.into_inner())?;
+    Ok((config, explode_span, reroll_span))
+}
+
+fn compile_faces(pair: Pair<Rule>) -> Result<FacesTerm, RogError> {
+    let inner = pair.into_inner().next().ok_or(RogErr::UnknownError)?;
+    Ok(match inner.as_rule() {
+        Rule::face_list => {
+            let faces = inner.into_inner().map(|p| compile_face(&p));
+            FacesTerm::List(faces.collect::<Result<_, _>>()?)
+        }
+        Rule::face_range => {
+            let mut bounds = inner.into_inner().map(|p| compile_face(&p));
+            let low = bounds.next().ok_or(RogErr::UnknownError)??;
+            let high = bounds.next().ok_or(RogErr::UnknownError)??;
+            FacesTerm::Range(low, high)
+        }
+        _ => FacesTerm::Sides(compile_number(&inner)),
+    })
+}
+
+/// Compiles a face of a custom die, which unlike other numbers may be negative.
+fn compile_face(pair: &Pair<Rule>) -> Result<Number, RogError> {
+    let mut number = None;
+    let mut negative = false;
+    for p in pair.clone().into_inner() {
+        match p.as_rule() {
+            Rule::face_neg => negative = true,
+            _ => number = Some(compile_number(&p)),
+        }
+    }
+    let mut number: Number = number.ok_or(RogErr::UnknownError)?;
+    if negative {
+        number.value = -number.value;
+    }
+    number.span = span_of(pair);
+    Ok(number)
+}
+
+fn compile_roll_config(pairs: Pairs<Rule>) -> Result<RollConfig<Number>, RogError> {
+    let mut config = RollConfig::new();
+    let mut written = vec![];
+    for pair in pairs {
+        // Unlike the other options, which add up, a second explosion or
+        // success count would silently replace the first.
+        if matches!(pair.as_rule(), Rule::explode | Rule::success) {
+            if written.contains(&pair.as_rule()) {
+                let (start, end) = span_of(&pair);
+                return Err(RogError::new(RogErr::OptionRepeated).at(start, end));
+            }
+            written.push(pair.as_rule());
+        }
+        let modifier = match pair.as_rule() {
+            Rule::unique => Modifier::Unique,
+            Rule::clamp_min | Rule::clamp_max => {
+                let number = pair
+                    .clone()
+                    .into_inner()
+                    .next()
+                    .ok_or(RogErr::UnknownError)?;
+                match pair.as_rule() {
+                    Rule::clamp_min => Modifier::Min(compile_number(&number)),
+                    _ => Modifier::Max(compile_number(&number)),
+                }
+            }
+            Rule::crit_success | Rule::crit_fail => {
+                let compare = pair
+                    .clone()
+                    .into_inner()
+                    .next()
+                    .ok_or(RogErr::UnknownError)?;
+                match pair.as_rule() {
+                    Rule::crit_success => Modifier::CritSuccess(compile_compare(
+                        compare.into_inner(),
+                        CompareOp::GreaterEq,
+                    )?),
+                    _ => Modifier::CritFail(compile_compare(
+                        compare.into_inner(),
+                        CompareOp::LessEq,
+                    )?),
+                }
+            }
+            Rule::aro => Modifier::Aro,
+            Rule::sort => Modifier::Sort,
+            Rule::keep_drop => {
+                let (keep_drop, value) = compile_keep_drop(pair.into_inner())?;
+                Modifier::KeepDrop(keep_drop, value)
+            }
+            Rule::success => {
+                let mut pairs = pair.into_inner();
+                let compare = pairs.next().ok_or(RogErr::UnknownError)?;
+                let compare = compile_compare(compare.into_inner(), CompareOp::GreaterEq)?;
+                let failure = match pairs.next() {
+                    Some(failure) => {
+                        let failure = failure.into_inner().next().ok_or(RogErr::UnknownError)?;
+                        Some(compile_compare(failure.into_inner(), CompareOp::Eq)?)
+                    }
+                    None => None,
+                };
+                Modifier::Success(Success { compare, failure })
+            }
+            Rule::reroll => {
+                let mut once = false;
+                let mut compare = None;
+                for x in pair.into_inner() {
+                    match x.as_rule() {
+                        Rule::reroll_once => once = true,
+                        Rule::compare => {
+                            compare = Some(compile_compare(x.into_inner(), CompareOp::Eq)?)
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                let compare = compare.ok_or(RogErr::UnknownError)?;
+                Modifier::Reroll(Reroll { once, compare })
+            }
+            Rule::explode => {
+                let mut kind = ExplodeKind::Explode;
+                let (mut compare, mut depth) = (None, None);
+                for x in pair.into_inner() {
+                    match x.as_rule() {
+                        Rule::compound => kind = ExplodeKind::Compound,
+                        Rule::penetrate => kind = ExplodeKind::Penetrate,
+                        Rule::compare => {
+                            compare = Some(compile_compare(x.into_inner(), CompareOp::GreaterEq)?)
+                        }
+                        Rule::explode_depth => {
+                            let number = x.into_inner().next().ok_or(RogErr::UnknownError)?;
+                            depth = Some(compile_number(&number));
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                Modifier::Explode {
+                    kind,
+                    compare,
+                    depth,
+                }
+            }
+            _ => unreachable!(),
+        };
+        config.modifiers.push(modifier);
+    }
+    Ok(config)
+}
+
+fn compile_keep_drop(mut pairs: Pairs<Rule>) -> Result<(KeepDrop, Number), RogError> {
+    let keep_drop = match pairs.next().ok_or(RogErr::UnknownError)?.as_rule() {
+        Rule::keep_high => KeepDrop::KeepHigh,
+        Rule::keep_low => KeepDrop::KeepLow,
+        Rule::drop_high => KeepDrop::DropHigh,
+        Rule::drop_low => KeepDrop::DropLow,
+        Rule::crit => KeepDrop::Crit,
+        _ => unreachable!(),
+    };
+    let value = compile_number(&pairs.next().ok_or(RogErr::UnknownError)?);
+    Ok((keep_drop, value))
+}
+
+/// Compiles a compare point, using `default` when it's just a number.
+fn compile_compare(pairs: Pairs<Rule>, default: CompareOp) -> Result<Compare<Number>, RogError> {
+    let mut op = default;
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::compare_eq => op = CompareOp::Eq,
+            Rule::compare_less => op = CompareOp::Less,
+            Rule::compare_less_eq => op = CompareOp::LessEq,
+            Rule::compare_greater => op = CompareOp::Greater,
+            Rule::compare_greater_eq => op = CompareOp::GreaterEq,
+            _ => {
+                return Ok(Compare {
+                    op,
+                    value: compile_number(&pair),
+                })
+            }
+        }
+    }
+    Err(RogErr::UnknownError.into())
+}
+
+fn compile_fate(primary: Pair<Rule>) -> Result<Expr, RogError> {
+    let span = span_of(&primary);
+    let (mut count, mut signs, mut options) = (None, None, None);
+    for pair in primary.into_inner() {
+        match pair.as_rule() {
+            Rule::dice_n => {
+                if !pair.as_str().is_empty() {
+                    count = Some(compile_number(&pair))
+                }
+            }
+            Rule::fate_signs => signs = pair.as_str()[1..].parse().ok(),
+            Rule::roll_config => options = Some(compile_options(pair, count.as_ref())?),
+            _ => unreachable!(),
+        }
+    }
+    let (config, explode_span, reroll_span) =
+        options.unwrap_or_else(|| (RollConfig::new(), None, None));
+    Ok(Expr::Fate(Box::new(FateTerm {
+        count,
+        signs,
+        config,
+        span,
+        explode_span,
+        reroll_span,
+    })))
+}
+
+fn compile_cards(primary: Pair<Rule>) -> Result<Expr, RogError> {
+    let mut term = CardsTerm {
+        count: None,
+        deck: None,
+        span: span_of(&primary),
+    };
+    for pair in primary.into_inner() {
+        match pair.as_rule() {
+            Rule::dice_n => term.count = (!pair.as_str().is_empty()).then(|| compile_number(&pair)),
+            Rule::deck_name => term.deck = Some(pair.as_str().to_string()),
+            _ => unreachable!(),
+        }
+    }
+    Ok(Expr::Cards(term))
+}
+
+fn compile_percentile(primary: Pair<Rule>) -> Result<Expr, RogError> {
+    let mut term = PercentileTerm {
+        count: None,
+        bonus: None,
+        penalty: None,
+        span: span_of(&primary),
+    };
+    for pair in primary.into_inner() {
+        // `b` and `p` alone mean a single bonus or penalty die.
+        let extra = |pair: Pair<Rule>| {
+            let span = span_of(&pair);
+            match pair.into_inner().next() {
+                Some(number) => compile_number(&number),
+                None => Number {
+                    value: 1.,
+                    attribute: None,
+                    span,
+                },
+            }
+        };
+        match pair.as_rule() {
+            Rule::dice_n => term.count = (!pair.as_str().is_empty()).then(|| compile_number(&pair)),
+            Rule::bonus => term.bonus = Some(extra(pair)),
+            Rule::penalty => term.penalty = Some(extra(pair)),
+            _ => unreachable!(),
+        }
+    }
+    Ok(Expr::Percentile(term))
+}
+
+fn get_parser_logic() -> PrattParser<Rule> {
+    PrattParser::new()
+        .op(Op::infix(Rule::and, Assoc::Left))
+        .op(Op::infix(Rule::or, Assoc::Left))
+        .op(Op::infix(Rule::counter_less, Assoc::Left)
+            | Op::infix(Rule::counter_greater, Assoc::Left))
+        .op(Op::infix(Rule::less, Assoc::Left)
+            | Op::infix(Rule::less_eq, Assoc::Left)
+            | Op::infix(Rule::greater, Assoc::Left)
+            | Op::infix(Rule::greater_eq, Assoc::Left)
+            | Op::infix(Rule::eq, Assoc::Left))
+        .op(Op::infix(Rule::add, Assoc::Left)
+            | Op::infix(Rule::sub, Assoc::Left)
+            | Op::infix(Rule::hyper_add, Assoc::Left)
+            | Op::infix(Rule::hyper_sub, Assoc::Left))
+        .op(Op::infix(Rule::mul, Assoc::Left)
+            | Op::infix(Rule::div, Assoc::Left)
+            | Op::infix(Rule::and, Assoc::Left)
+            | Op::infix(Rule::or, Assoc::Left))
+        .op(Op::postfix(Rule::percent))
+        .op(Op::prefix(Rule::ceil) | Op::prefix(Rule::round) | Op::prefix(Rule::floor))
+        .op(Op::prefix(Rule::neg) | Op::prefix(Rule::not))
+}
+
+impl From<pest::error::Error<Rule>> for RogError {
+    fn from(err: pest::error::Error<Rule>) -> Self {
+        let (start, end) = match err.location {
+            InputLocation::Pos(pos) => (pos, pos),
+            InputLocation::Span(span) => span,
+        };
+        let expected = match err.variant {
+            ErrorVariant::ParsingError { positives, .. } => {
+                positives.iter().map(|rule| format!("{:?}", rule)).collect()
+            }
+            ErrorVariant::CustomError { .. } => vec![],
+        };
+        RogError {
+            expected,
+            ..RogError::new(RogErr::Syntax).at(start, end)
+        }
+    }
+}
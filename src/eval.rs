@@ -0,0 +1,369 @@
+use crate::rog::*;
+use std::collections::HashMap;
+
+/// Runs every line of a compiled script, rolling the dice with `roller`
+/// and writing assignments to `attr_map`.
+pub fn eval(
+    script: &Script,
+    attr_map: &mut HashMap<String, f64>,
+    roller: &mut Roller,
+) -> Result<Vec<RogCons>, RogError> {
+    let mut cons = vec![];
+    for line in &script.lines {
+        roller.set_line(line.number);
+        eval_line(line, attr_map, roller, &mut cons)
+            .map_err(|e| e.locate(line.number, line.offset))?;
+    }
+    Ok(cons)
+}
+
+fn eval_line(
+    line: &Line,
+    attr_map: &mut HashMap<String, f64>,
+    roller: &mut Roller,
+    buf: &mut Vec<RogCons>,
+) -> Result<(), RogError> {
+    for _ in 0..line.repeat {
+        if buf.len() >= MAX_LINES {
+            return Err(RogErr::LineMax.into());
+        }
+        let mut current_cons = eval_assignment(line, attr_map, roller)?;
+        if let Some(c) = &line.comment {
+            current_cons.text = format!("**{}** {}", c, current_cons.text);
+        }
+        buf.push(current_cons);
+    }
+    Ok(())
+}
+
+fn eval_assignment(
+    line: &Line,
+    attr_map: &mut HashMap<String, f64>,
+    roller: &mut Roller,
+) -> Result<RogCons, RogError> {
+    let mut result = eval_expression(&line.expression, attr_map, roller)?;
+
+    // Sheets may already be over `MAX_ATTRIBUTES`, only new keys are refused.
+    let keys = attr_map.len();
+    // The margin of the last test can be used by the lines after it. Set
+    // before the assignment, so both count towards `MAX_ATTRIBUTES`.
+    if let (Some(margin), Some(degrees)) = (result.margin, result.degrees) {
+        attr_map.insert("$MARGEM".to_string(), margin);
+        attr_map.insert("$GRAUS".to_string(), degrees as f64);
+    }
+    if let Some(assignment) = &line.assignment {
+        let (start, end) = assignment.span;
+        let key = assignment.target.as_str();
+        if !key.starts_with('$') && !attr_map.contains_key(key) {
+            return Err(RogError::new(RogErr::InvalidAttribute(key.to_string())).at(start, end));
+        }
+
+        result.value = *attr_map
+            .entry(key.to_string())
+            .and_modify(|e| match assignment.op {
+                AssignOp::Eq => *e = result.value,
+                AssignOp::Add => *e += result.value,
+                AssignOp::Sub => *e -= result.value,
+                AssignOp::Mul => *e *= result.value,
+                AssignOp::Div => *e /= result.value,
+            })
+            .or_insert_with(|| match assignment.op {
+                AssignOp::Eq => result.value,
+                AssignOp::Add => result.value,
+                AssignOp::Sub => -result.value,
+                AssignOp::Mul => 0.,
+                AssignOp::Div => 0.,
+            });
+
+        let op_str = match assignment.op {
+            AssignOp::Eq => "=",
+            AssignOp::Add => "+=",
+            AssignOp::Sub => "-=",
+            AssignOp::Mul => ":\\*",
+            AssignOp::Div => "/=",
+        };
+
+        result.text = format!("{} {} {}", key, op_str, result.text);
+        result.dice += 1;
+    }
+    if attr_map.len() > keys && attr_map.len() > MAX_ATTRIBUTES {
+        let err = RogError::new(RogErr::AttributeMax);
+        return Err(match &line.assignment {
+            Some(assignment) => err.at(assignment.span.0, assignment.span.1),
+            None => err,
+        });
+    }
+    result.text = if result.boolean {
+        let verdict = match (result.tier.as_deref(), result.value != 0.) {
+            (Some("critical"), _) => "**Sucesso Crítico!**",
+            (Some("extreme"), _) => "**Sucesso Extremo!**",
+            (Some("hard"), _) => "**Sucesso Difícil!**",
+            (Some("fumble"), _) => "**Desastre!**",
+            (_, true) => "**Sucesso!**",
+            (_, false) => "**Falha!**",
+        };
+        let details = match (result.margin, result.degrees) {
+            (Some(margin), Some(1)) => format!(" (margem {}, 1 grau)", margin),
+            (Some(margin), Some(degrees)) => format!(" (margem {}, {} graus)", margin, degrees),
+            _ => String::new(),
+        };
+        format!("` {}{} ` ⟵ {}", verdict, details, result.text)
+    } else {
+        let ladder = if line.expression.on_fate_ladder() {
+            FateDice::ladder(result.value)
+        } else {
+            None
+        };
+        match ladder {
+            Some(ladder) => format!("` {} {} ` ⟵ {}", result.value, ladder, result.text),
+            None => format!("` {} ` ⟵ {}", result.value, result.text),
+        }
+    };
+
+    Ok(result)
+}
+
+fn eval_expression(
+    expr: &Expr,
+    attr_map: &HashMap<String, f64>,
+    roller: &mut Roller,
+) -> Result<RogCons, RogError> {
+    Ok(match expr {
+        Expr::Number(number) => {
+            let res = eval_number(number, attr_map)?;
+            match number.attribute {
+                Some(_) => RogCons::from_number(res, format!("[{}] {}", res, number)),
+                None => RogCons::from_number(res, String::new()),
+            }
+        }
+        Expr::Dice(term) => roll_dice(term, attr_map, roller)?,
+        Expr::Fate(term) => roll_fate(term, attr_map, roller)?,
+        Expr::Percentile(term) => roll_percentile(term, attr_map, roller)?,
+        Expr::Cards(term) => draw_cards(term, attr_map, roller)?,
+        Expr::Group(inner) => {
+            let mut exp = eval_expression(inner, attr_map, roller)?;
+            exp.text = format!("({})", exp.text);
+            exp
+        }
+        Expr::Prefix(op, rhs) => {
+            let rhs = eval_expression(rhs, attr_map, roller)?;
+            match op {
+                PrefixOp::Neg => -rhs,
+                PrefixOp::Not => !rhs,
+                PrefixOp::Ceil => rhs.ceil(),
+                PrefixOp::Round => rhs.round(),
+                PrefixOp::Floor => rhs.floor(),
+            }
+        }
+        Expr::Postfix(op, lhs) => {
+            let lhs = eval_expression(lhs, attr_map, roller)?;
+            match op {
+                PostfixOp::Percent => lhs.percent(),
+            }
+        }
+        Expr::Infix(op, lhs_expr, rhs) => {
+            let lhs = eval_expression(lhs_expr, attr_map, roller)?;
+            let rhs = eval_expression(rhs, attr_map, roller)?;
+            match op {
+                InfixOp::And => lhs & rhs,
+                InfixOp::Or => lhs | rhs,
+                // A single `d%` rolled under a skill is graded.
+                InfixOp::LessEq
+                    if matches!(**lhs_expr, Expr::Percentile(_)) && lhs.values.len() == 1 =>
+                {
+                    lhs.skill_check(rhs)
+                }
+                InfixOp::LessEq => lhs.less_eq(rhs),
+                InfixOp::Less => lhs.less(rhs),
+                InfixOp::GreaterEq => lhs.greater_eq(rhs),
+                InfixOp::Greater => lhs.greater(rhs),
+                InfixOp::Eq => lhs.eq(rhs),
+                InfixOp::HyperAdd => lhs.hyper_add(rhs),
+                InfixOp::HyperSub => lhs.hyper_sub(rhs),
+                InfixOp::CounterLess => lhs << rhs,
+                InfixOp::CounterGreater => lhs >> rhs,
+                InfixOp::Add => lhs + rhs,
+                InfixOp::Sub => lhs - rhs,
+                InfixOp::Mul => lhs * rhs,
+                InfixOp::Div => lhs / rhs,
+            }
+        }
+    })
+}
+
+fn eval_number(number: &Number, attr_map: &HashMap<String, f64>) -> Result<f64, RogError> {
+    match &number.attribute {
+        Some(attr) => {
+            let value = attr_map.get(attr).ok_or_else(|| {
+                RogError::new(RogErr::InvalidAttribute(attr.to_string()))
+                    .at(number.span.0, number.span.1)
+            })?;
+            Ok(number.value * value)
+        }
+        None => Ok(number.value),
+    }
+}
+
+/// Faces of the face set `name`, kept in the attribute map as `NAME.1`,
+/// `NAME.2` and so on, if there is one.
+fn face_set(name: &str, attr_map: &HashMap<String, f64>) -> Option<Vec<f64>> {
+    let faces: Vec<f64> = (1..)
+        .map_while(|i| attr_map.get(&format!("{}.{}", name, i)).copied())
+        .collect();
+    (!faces.is_empty()).then_some(faces)
+}
+
+fn eval_faces(term: &FacesTerm, attr_map: &HashMap<String, f64>) -> Result<Faces, RogError> {
+    let set_of = |number: &Number| {
+        let faces = face_set(number.attribute.as_deref()?, attr_map)?;
+        Some(faces.into_iter().map(|face| number.value * face).collect())
+    };
+    Ok(match term {
+        FacesTerm::Sides(sides) => match set_of(sides) {
+            Some(faces) => Faces::List(faces),
+            None => Faces::Sides(eval_number(sides, attr_map)? as usize),
+        },
+        FacesTerm::List(numbers) => {
+            let mut faces = vec![];
+            for number in numbers {
+                match set_of(number) {
+                    Some(set) => faces.extend(set),
+                    None => faces.push(eval_number(number, attr_map)?),
+                }
+            }
+            Faces::List(faces)
+        }
+        FacesTerm::Range(low, high) => {
+            let (low, high) = (eval_number(low, attr_map)?, eval_number(high, attr_map)?);
+            // Every face has to be counted by an `i64`, as does their number.
+            let range = i64::MIN as f64..i64::MAX as f64;
+            let faces = high as i128 - low as i128 + 1;
+            if !range.contains(&low) || !range.contains(&high) || faces > i64::MAX as i128 {
+                let err = RogError::new(RogErr::RangeMax(low, high));
+                let (start, end) = term.span();
+                return Err(err.at(start, end));
+            }
+            Faces::Range(low as i64, high as i64)
+        }
+    })
+}
+
+fn spanned(err: RogErr, span: Option<Span>) -> RogError {
+    let err = RogError::new(err);
+    match span {
+        Some((start, end)) => err.at(start, end),
+        None => err,
+    }
+}
+
+fn roll_dice(
+    term: &DiceTerm,
+    attr_map: &HashMap<String, f64>,
+    roller: &mut Roller,
+) -> Result<RogCons, RogError> {
+    let mut dice = Dice::new();
+    if let Some(count) = &term.count {
+        dice.count = eval_number(count, attr_map)? as usize;
+    }
+    dice.faces = eval_faces(&term.faces, attr_map)?;
+    dice.config = term
+        .config
+        .try_map(|n| eval_number(n, attr_map).map(|v| v as usize))?;
+
+    dice.roll(roller).map_err(|e| {
+        let span = match e {
+            RogErr::CountMax(_) => term.count.as_ref().map(|n| n.span),
+            RogErr::CountMin(_) => Some(term.faces.span()),
+            RogErr::ExplodeMin(_) | RogErr::ExplodeChanceHigh(..) | RogErr::ExplodeDepth(_) => {
+                term.explode_span
+            }
+            RogErr::RerollAll(_) | RogErr::RerollMax => term.reroll_span,
+            _ => None,
+        };
+        spanned(e, span.or(Some(term.span)))
+    })
+}
+
+fn roll_fate(
+    term: &FateTerm,
+    attr_map: &HashMap<String, f64>,
+    roller: &mut Roller,
+) -> Result<RogCons, RogError> {
+    let mut dice = FateDice::new();
+    if let Some(count) = &term.count {
+        dice.count = eval_number(count, attr_map)? as usize;
+    }
+    dice.signs = term.signs;
+    dice.config = term
+        .config
+        .try_map(|n| eval_number(n, attr_map).map(|v| v as usize))?;
+
+    dice.roll(roller).map_err(|e| {
+        let span = match e {
+            RogErr::CountMax(_) => term.count.as_ref().map(|n| n.span),
+            RogErr::ExplodeChanceHigh(..) | RogErr::ExplodeDepth(_) => term.explode_span,
+            RogErr::RerollAll(_) | RogErr::RerollMax => term.reroll_span,
+            _ => None,
+        };
+        spanned(e, span.or(Some(term.span)))
+    })
+}
+
+fn roll_percentile(
+    term: &PercentileTerm,
+    attr_map: &HashMap<String, f64>,
+    roller: &mut Roller,
+) -> Result<RogCons, RogError> {
+    let mut dice = PercentileDice::new();
+    if let Some(count) = &term.count {
+        dice.count = eval_number(count, attr_map)? as usize;
+    }
+    if let Some(bonus) = &term.bonus {
+        dice.bonus = eval_number(bonus, attr_map)? as usize;
+    }
+    if let Some(penalty) = &term.penalty {
+        dice.penalty = eval_number(penalty, attr_map)? as usize;
+    }
+    let count = dice.count;
+    dice.roll(roller).map_err(|e| {
+        let span = match e {
+            RogErr::CountMax(extra) if extra != count => term
+                .bonus
+                .as_ref()
+                .or(term.penalty.as_ref())
+                .map(|n| n.span),
+            _ => term.count.as_ref().map(|n| n.span),
+        };
+        spanned(e, Some(span.unwrap_or(term.span)))
+    })
+}
+
+fn draw_cards(
+    term: &CardsTerm,
+    attr_map: &HashMap<String, f64>,
+    roller: &mut Roller,
+) -> Result<RogCons, RogError> {
+    let mut cards = Cards::new();
+    if let Some(count) = &term.count {
+        cards.count = eval_number(count, attr_map)? as usize;
+    }
+    if let Some(name) = &term.deck {
+        cards.deck = match Deck::builtin(name) {
+            Some(deck) => deck,
+            None => match face_set(name, attr_map) {
+                Some(faces) => Deck::Custom(name.clone(), faces),
+                None => {
+                    let err = RogError::new(RogErr::InvalidAttribute(name.clone()));
+                    return Err(err.at(term.span.0, term.span.1));
+                }
+            },
+        };
+    }
+    cards.draw(roller).map_err(|e| {
+        let span = match e {
+            RogErr::CountMax(_) | RogErr::DrawMax(_) => term.count.as_ref().map(|n| n.span),
+            _ => None,
+        };
+        spanned(e, span.or(Some(term.span)))
+    })
+}
@@ -0,0 +1,225 @@
+use crate::rog::*;
+use crate::*;
+use napi_derive::napi;
+use std::collections::HashMap;
+
+/// Failure of a `parse` call, as seen from JS.
+#[napi(object)]
+#[derive(Debug)]
+pub struct ParseError {
+    /// Stable error identifier, see `RogErr::code`.
+    pub code: String,
+    /// Message for the user, in the requested locale.
+    pub message: String,
+    /// 1-based line of the input where the error happened.
+    pub line: u32,
+    /// Byte offsets in the input of the offending part, when known.
+    pub start: Option<u32>,
+    pub end: Option<u32>,
+    /// Grammar rules that would have been accepted, for syntax errors.
+    pub expected: Vec<String>,
+    /// The error rendered as text, pointing at the offending part of the line.
+    pub diagnostic: String,
+    pub labels: Vec<DiagnosticLabel>,
+}
+impl ParseError {
+    fn new(err: RogError, input: &str, locale: Locale) -> Self {
+        let diagnostic = Diagnostic::new(&err, input, locale);
+        Self {
+            diagnostic: diagnostic.render(),
+            labels: diagnostic
+                .labels
+                .into_iter()
+                .map(|label| DiagnosticLabel {
+                    start: label.start as u32,
+                    end: label.end as u32,
+                    message: label.message,
+                })
+                .collect(),
+            code: err.kind.code().to_string(),
+            message: err.kind.message(locale),
+            line: err.line as u32,
+            start: err.span.map(|(start, _)| start as u32),
+            end: err.span.map(|(_, end)| end as u32),
+            expected: err.expected,
+        }
+    }
+}
+
+/// A marked part of the input, in byte offsets.
+#[napi(object)]
+#[derive(Debug)]
+pub struct DiagnosticLabel {
+    pub start: u32,
+    pub end: u32,
+    pub message: String,
+}
+
+/// Either `output` (when `ok`) or `error` is set.
+#[napi(object)]
+#[derive(Debug)]
+pub struct ParseResult {
+    pub ok: bool,
+    pub output: Option<Output>,
+    pub error: Option<ParseError>,
+}
+
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct ParseOptions {
+    /// Language tag for error messages (`pt-BR` or `en`), defaults to `pt-BR`.
+    pub locale: Option<String>,
+    /// Hexadecimal seed to roll with, a random one is used when missing.
+    pub seed: Option<String>,
+    /// Rolls with a seed derived from `secret` and `nonce`, leaving the seed
+    /// out of the output. The commitment of the secret, see `commit`, must be
+    /// published before the nonce is chosen. Both are required together, and
+    /// can't be given along with `seed`.
+    pub secret: Option<String>,
+    pub nonce: Option<String>,
+    /// Cards left in each deck, as returned in `Output.decks` by an earlier call.
+    pub decks: Option<HashMap<String, Vec<u32>>>,
+}
+
+impl ParseOptions {
+    fn locale(&self) -> Locale {
+        self.locale
+            .as_deref()
+            .and_then(Locale::from_tag)
+            .unwrap_or_default()
+    }
+    fn seed(&self) -> Result<Seed, RogErr> {
+        Seed::from_options(
+            self.seed.as_deref(),
+            self.secret.as_deref(),
+            self.nonce.as_deref(),
+        )
+    }
+}
+
+impl ParseResult {
+    fn new(result: Result<Output, RogError>, input: &str, locale: Locale) -> Self {
+        match result {
+            Ok(output) => Self {
+                ok: true,
+                output: Some(output),
+                error: None,
+            },
+            Err(err) => Self {
+                ok: false,
+                output: None,
+                error: Some(ParseError::new(err, input, locale)),
+            },
+        }
+    }
+}
+
+fn evaluate(
+    input: &str,
+    script: Result<&Script, &RogError>,
+    attr_map: HashMap<String, f64>,
+    options: Option<ParseOptions>,
+) -> ParseResult {
+    let options = options.unwrap_or_default();
+    let result = script.map_err(Clone::clone).and_then(|script| {
+        let decks = options.decks.clone().unwrap_or_default();
+        let mut output = run_script(script, attr_map, decks, options.seed()?)?;
+        if let Some(secret) = &options.secret {
+            output.seed = None;
+            output.commitment = Some(Seed::commitment(secret));
+        }
+        Ok(output)
+    });
+    ParseResult::new(result, input, options.locale())
+}
+
+#[napi]
+pub fn parse(
+    input: String,
+    attr_map: HashMap<String, f64>,
+    options: Option<ParseOptions>,
+) -> ParseResult {
+    evaluate(&input, compile(&input).as_ref(), attr_map, options)
+}
+
+/// A script compiled once, to be evaluated many times with different
+/// attributes and seeds.
+#[napi(js_name = "Script")]
+pub struct CompiledScript {
+    input: String,
+    script: Result<Script, RogError>,
+}
+
+#[napi]
+impl CompiledScript {
+    #[napi(constructor)]
+    pub fn new(input: String) -> Self {
+        let script = compile(&input);
+        Self { input, script }
+    }
+    /// Whether the script compiled, `error` tells why when it didn't.
+    #[napi(getter)]
+    pub fn ok(&self) -> bool {
+        self.script.is_ok()
+    }
+    #[napi]
+    pub fn error(&self, locale: Option<String>) -> Option<ParseError> {
+        let locale = ParseOptions {
+            locale,
+            ..Default::default()
+        }
+        .locale();
+        self.script
+            .as_ref()
+            .err()
+            .map(|err| ParseError::new(err.clone(), &self.input, locale))
+    }
+    /// Attributes the script uses, which must be in the attribute map.
+    #[napi(getter)]
+    pub fn attributes(&self) -> Vec<String> {
+        self.script.as_ref().map_or(vec![], Script::attributes)
+    }
+    /// Names the script rolls as faces or draws from as decks, which must be
+    /// in the attribute map as face sets (`MED.1`, `MED.2`, ...). Dice faces
+    /// may instead be attributes with the number of sides.
+    #[napi(getter)]
+    pub fn face_sets(&self) -> Vec<String> {
+        self.script.as_ref().map_or(vec![], Script::face_sets)
+    }
+    /// `$` variables the script uses.
+    #[napi(getter)]
+    pub fn variables(&self) -> Vec<String> {
+        self.script.as_ref().map_or(vec![], Script::variables)
+    }
+    /// Same as `parse`, reusing the compiled script.
+    #[napi]
+    pub fn eval(
+        &self,
+        attr_map: HashMap<String, f64>,
+        options: Option<ParseOptions>,
+    ) -> ParseResult {
+        evaluate(&self.input, self.script.as_ref(), attr_map, options)
+    }
+}
+
+/// Commitment of a GM secret, to be published before rolling with it.
+#[napi]
+pub fn commit(secret: String) -> String {
+    Seed::commitment(&secret)
+}
+
+/// Checks a roll made with `secret` and `nonce` against the commitment
+/// published before it, once the GM reveals the secret.
+#[napi]
+pub fn verify(
+    input: String,
+    attr_map: HashMap<String, f64>,
+    secret: String,
+    nonce: String,
+    commitment: String,
+    cons: Vec<RogCons>,
+    decks: Option<HashMap<String, Vec<u32>>>,
+) -> bool {
+    let decks = decks.unwrap_or_default();
+    verify_run(&input, attr_map, decks, &secret, &nonce, &commitment, &cons)
+}
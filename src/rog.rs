@@ -2,3 +2,15 @@ mod rogcons;
 pub use rogcons::*;
 mod dice;
 pub use dice::*;
+mod deck;
+pub use deck::*;
+mod error;
+pub use error::*;
+mod locale;
+pub use locale::*;
+mod diagnostic;
+pub use diagnostic::*;
+mod roller;
+pub use roller::*;
+mod ast;
+pub use ast::*;
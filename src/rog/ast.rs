@@ -0,0 +1,319 @@
+use super::deck::Deck;
+use super::dice::*;
+
+/// Byte offsets of a node, relative to the start of its line.
+pub type Span = (usize, usize);
+
+/// A number as written in the script: either a literal, or an attribute
+/// or variable scaled by a literal (`FOR`, `2FOR`, `$DANO`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Number {
+    /// The literal, or the factor the attribute is multiplied by.
+    pub value: f64,
+    pub attribute: Option<String>,
+    pub span: Span,
+}
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.attribute {
+            Some(attribute) if self.value == 1. => write!(f, "{}", attribute),
+            Some(attribute) => write!(f, "{}{}", self.value, attribute),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+/// The faces of a die as written: `d6`, `d{0,1,1,2}` or `d[-3..3]`.
+/// A number of sides naming a face set, such as `dAVG`, rolls that set.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FacesTerm {
+    Sides(Number),
+    /// Each face, where a face set expands into all of its faces.
+    List(Vec<Number>),
+    Range(Number, Number),
+}
+impl FacesTerm {
+    pub fn numbers(&self) -> Vec<&Number> {
+        match self {
+            FacesTerm::Sides(sides) => vec![sides],
+            FacesTerm::List(faces) => faces.iter().collect(),
+            FacesTerm::Range(low, high) => vec![low, high],
+        }
+    }
+    pub fn span(&self) -> Span {
+        let numbers = self.numbers();
+        let start = numbers.first().map_or(0, |n| n.span.0);
+        let end = numbers.last().map_or(0, |n| n.span.1);
+        (start, end)
+    }
+}
+
+/// A `XdY` term, along with its roll options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiceTerm {
+    pub count: Option<Number>,
+    pub faces: FacesTerm,
+    pub config: RollConfig<Number>,
+    pub span: Span,
+    /// Where the explosion option was written, if it was.
+    pub explode_span: Option<Span>,
+    /// Where the reroll option was written, if it was.
+    pub reroll_span: Option<Span>,
+}
+
+/// A `Xdf` term, along with its roll options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FateTerm {
+    pub count: Option<Number>,
+    /// `-` and `+` faces of a Fudge die, `df.1` or `df.2`.
+    pub signs: Option<usize>,
+    pub config: RollConfig<Number>,
+    pub span: Span,
+    /// Where the explosion option was written, if it was.
+    pub explode_span: Option<Span>,
+    /// Where the reroll option was written, if it was.
+    pub reroll_span: Option<Span>,
+}
+
+/// A `Xc` term, drawing from the deck named after it or from `BARALHO`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardsTerm {
+    pub count: Option<Number>,
+    pub deck: Option<String>,
+    pub span: Span,
+}
+
+/// A `Xd%` term, with its bonus (`b2`) and penalty (`p1`) dice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PercentileTerm {
+    pub count: Option<Number>,
+    pub bonus: Option<Number>,
+    pub penalty: Option<Number>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixOp {
+    Neg,
+    Not,
+    Ceil,
+    Round,
+    Floor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostfixOp {
+    Percent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfixOp {
+    And,
+    Or,
+    LessEq,
+    Less,
+    GreaterEq,
+    Greater,
+    Eq,
+    HyperAdd,
+    HyperSub,
+    CounterLess,
+    CounterGreater,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(Number),
+    Dice(Box<DiceTerm>),
+    Fate(Box<FateTerm>),
+    Percentile(PercentileTerm),
+    Cards(CardsTerm),
+    Group(Box<Expr>),
+    Prefix(PrefixOp, Box<Expr>),
+    Postfix(PostfixOp, Box<Expr>),
+    Infix(InfixOp, Box<Expr>, Box<Expr>),
+}
+impl Expr {
+    /// Every number written in the expression, including dice counts and options.
+    pub fn numbers(&self) -> Vec<&Number> {
+        match self {
+            Expr::Number(number) => vec![number],
+            Expr::Dice(term) => {
+                let mut numbers: Vec<&Number> = term.count.iter().collect();
+                numbers.extend(term.faces.numbers());
+                numbers.extend(term.config.numbers());
+                numbers
+            }
+            Expr::Fate(term) => {
+                let mut numbers: Vec<&Number> = term.count.iter().collect();
+                numbers.extend(term.config.numbers());
+                numbers
+            }
+            Expr::Cards(term) => term.count.iter().collect(),
+            Expr::Percentile(term) => {
+                let numbers = term.count.iter().chain(&term.bonus).chain(&term.penalty);
+                numbers.collect()
+            }
+            Expr::Group(inner) | Expr::Prefix(_, inner) | Expr::Postfix(_, inner) => {
+                inner.numbers()
+            }
+            Expr::Infix(_, lhs, rhs) => {
+                let mut numbers = lhs.numbers();
+                numbers.extend(rhs.numbers());
+                numbers
+            }
+        }
+    }
+    /// Numbers that may name a face set rather than an attribute: the sides
+    /// of a die (`2dMED`) and the faces in a list (`d{MED,10}`).
+    pub fn face_sets(&self) -> Vec<&Number> {
+        match self {
+            Expr::Dice(term) => match &term.faces {
+                FacesTerm::Sides(sides) => vec![sides],
+                FacesTerm::List(faces) => faces.iter().collect(),
+                FacesTerm::Range(..) => vec![],
+            },
+            Expr::Group(inner) | Expr::Prefix(_, inner) | Expr::Postfix(_, inner) => {
+                inner.face_sets()
+            }
+            Expr::Infix(_, lhs, rhs) => {
+                let mut numbers = lhs.face_sets();
+                numbers.extend(rhs.face_sets());
+                numbers
+            }
+            _ => vec![],
+        }
+    }
+    /// Names of the custom decks cards are drawn from, leaving out the
+    /// built-in ones such as `BARALHO`.
+    pub fn decks(&self) -> Vec<&str> {
+        match self {
+            Expr::Cards(term) => term
+                .deck
+                .as_deref()
+                .filter(|name| Deck::builtin(name).is_none())
+                .into_iter()
+                .collect(),
+            Expr::Group(inner) | Expr::Prefix(_, inner) | Expr::Postfix(_, inner) => inner.decks(),
+            Expr::Infix(_, lhs, rhs) => {
+                let mut names = lhs.decks();
+                names.extend(rhs.decks());
+                names
+            }
+            _ => vec![],
+        }
+    }
+    /// Whether the expression adds up Fate dice, and so reads on the Fate
+    /// ladder. Counting them, with `<<`, `>>` or as a success pool, doesn't.
+    pub fn on_fate_ladder(&self) -> bool {
+        match self {
+            Expr::Fate(term) => !term
+                .config
+                .modifiers
+                .iter()
+                .any(|modifier| matches!(modifier, Modifier::Success(_))),
+            Expr::Group(inner) | Expr::Prefix(_, inner) | Expr::Postfix(_, inner) => {
+                inner.on_fate_ladder()
+            }
+            Expr::Infix(InfixOp::CounterLess | InfixOp::CounterGreater, ..) => false,
+            Expr::Infix(_, lhs, rhs) => lhs.on_fate_ladder() || rhs.on_fate_ladder(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignOp {
+    Eq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// The `ATR +=` part of a line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    /// Attribute or `$` variable being assigned to.
+    pub target: String,
+    pub op: AssignOp,
+    pub span: Span,
+}
+
+/// A single line of a script, such as `3# $DANO += 2d6 ; Ataque`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// 1-based line number in the script.
+    pub number: usize,
+    /// Byte offset of the line in the script.
+    pub offset: usize,
+    pub repeat: usize,
+    pub assignment: Option<Assignment>,
+    pub expression: Expr,
+    pub comment: Option<String>,
+}
+
+/// A compiled script, ready to be evaluated any number of times.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Script {
+    pub lines: Vec<Line>,
+}
+impl Script {
+    /// Attributes the script reads or assigns to, sorted and without repeats.
+    /// They must all be in the attribute map when the script is evaluated.
+    /// Names only rolled as faces are left out, see `face_sets`.
+    pub fn attributes(&self) -> Vec<String> {
+        self.names(|name| !name.starts_with('$'))
+    }
+    /// `$` variables the script reads or assigns to, sorted and without repeats.
+    pub fn variables(&self) -> Vec<String> {
+        self.names(|name| name.starts_with('$'))
+    }
+    /// Names rolled as faces, as in `2dMED`, or drawn from as decks, as in
+    /// `cINICIATIVA`, sorted and without repeats. Each must be in the
+    /// attribute map as a face set, one key per face (`MED.1`, `MED.2` and so
+    /// on). Dice faces may instead be an attribute with the number of sides.
+    pub fn face_sets(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .lines
+            .iter()
+            .flat_map(|line| {
+                let faces = line.expression.face_sets().into_iter();
+                let faces = faces.filter_map(|number| number.attribute.as_deref());
+                faces.chain(line.expression.decks()).collect::<Vec<_>>()
+            })
+            .filter(|name| !name.starts_with('$'))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+    fn names(&self, filter: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .lines
+            .iter()
+            .flat_map(|line| {
+                let face_sets = line.expression.face_sets();
+                let read = line.expression.numbers().into_iter();
+                // `$` variables can't hold face sets, so they are always read.
+                let read = read.filter_map(|number| {
+                    let name = number.attribute.as_deref()?;
+                    let face_set = face_sets.iter().any(|set| std::ptr::eq(*set, number));
+                    (name.starts_with('$') || !face_set).then_some(name)
+                });
+                let assigned = line.assignment.iter().map(|a| a.target.as_str());
+                read.chain(assigned).collect::<Vec<_>>()
+            })
+            .filter(|name| filter(name))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
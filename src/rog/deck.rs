@@ -0,0 +1,175 @@
+use super::error::*;
+use super::rogcons::*;
+use super::roller::*;
+
+const SUITS: [&str; 4] = ["♣", "♦", "♥", "♠"];
+const RANKS: [&str; 13] = [
+    "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K", "A",
+];
+const TAROT_SUITS: [&str; 4] = ["Copas", "Ouros", "Espadas", "Paus"];
+const TAROT_RANKS: [&str; 14] = [
+    "Ás",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "7",
+    "8",
+    "9",
+    "10",
+    "Valete",
+    "Cavaleiro",
+    "Rainha",
+    "Rei",
+];
+const MAJOR_ARCANA: [&str; 22] = [
+    "O Louco",
+    "O Mago",
+    "A Sacerdotisa",
+    "A Imperatriz",
+    "O Imperador",
+    "O Hierofante",
+    "Os Enamorados",
+    "O Carro",
+    "A Justiça",
+    "O Eremita",
+    "A Roda da Fortuna",
+    "A Força",
+    "O Enforcado",
+    "A Morte",
+    "A Temperança",
+    "O Diabo",
+    "A Torre",
+    "A Estrela",
+    "A Lua",
+    "O Sol",
+    "O Julgamento",
+    "O Mundo",
+];
+
+/// A deck of cards, drawn from without replacement until it runs out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Deck {
+    /// `BARALHO`, 52 cards from 2 (worth 2) to the ace (worth 14).
+    Standard,
+    /// `CORINGAS`, the standard deck and two jokers, worth 15.
+    Jokers,
+    /// `TAROT`, the 22 major arcana, worth their number, and 56 minor arcana
+    /// worth their rank, from the ace (1) to the king (14).
+    Tarot,
+    /// A face set of the attribute map, with its name.
+    Custom(String, Vec<f64>),
+}
+impl Deck {
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "BARALHO" => Some(Deck::Standard),
+            "CORINGAS" => Some(Deck::Jokers),
+            "TAROT" => Some(Deck::Tarot),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &str {
+        match self {
+            Deck::Standard => "BARALHO",
+            Deck::Jokers => "CORINGAS",
+            Deck::Tarot => "TAROT",
+            Deck::Custom(name, _) => name,
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Deck::Standard => 52,
+            Deck::Jokers => 54,
+            Deck::Tarot => 78,
+            Deck::Custom(_, cards) => cards.len(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Value and name of the card at `index`.
+    fn card(&self, index: usize) -> (f64, String) {
+        match self {
+            Deck::Standard | Deck::Jokers if index >= 52 => (15., "🃏".to_string()),
+            Deck::Standard | Deck::Jokers => {
+                let (suit, rank) = (index / 13, index % 13);
+                ((rank + 2) as f64, format!("{}{}", RANKS[rank], SUITS[suit]))
+            }
+            Deck::Tarot if index < 22 => (index as f64, MAJOR_ARCANA[index].to_string()),
+            Deck::Tarot => {
+                let (suit, rank) = ((index - 22) / 14, (index - 22) % 14);
+                let name = format!("{} de {}", TAROT_RANKS[rank], TAROT_SUITS[suit]);
+                ((rank + 1) as f64, name)
+            }
+            Deck::Custom(_, cards) => (cards[index], cards[index].to_string()),
+        }
+    }
+}
+
+/// `Xc`, draws `X` cards from a deck. The cards left stay in the `Roller`.
+pub struct Cards {
+    pub count: usize,
+    pub deck: Deck,
+}
+impl Cards {
+    pub fn new() -> Self {
+        Self {
+            count: 1,
+            deck: Deck::Standard,
+        }
+    }
+    pub fn draw(self, roller: &mut Roller) -> Result<RogCons, RogErr> {
+        if self.count > MAX_DICE {
+            return Err(RogErr::CountMax(self.count));
+        }
+        let size = self.deck.len();
+        if self.count > size {
+            return Err(RogErr::DrawMax(size));
+        }
+        let mut hand = vec![];
+        for _ in 0..self.count {
+            hand.push(roller.draw(self.deck.name(), size, &hand));
+        }
+        let cards: Vec<(f64, String)> = hand.iter().map(|index| self.deck.card(*index)).collect();
+        let values: Vec<f64> = cards.iter().map(|(value, _)| *value).collect();
+
+        let mut text = cards
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let notation = match self.deck {
+            Deck::Standard => format!("{}c", self.count),
+            _ => format!("{}c{}", self.count, self.deck.name()),
+        };
+        text = format!("[{}] {}", text, notation);
+
+        roller.record(
+            &notation,
+            size,
+            values.iter().map(|value| (*value, RollKind::Roll, true)),
+        );
+
+        Ok(RogCons {
+            value: values.iter().sum(),
+            text,
+            values,
+            boolean: false,
+            dice: self.count as u32,
+            glitch: false,
+            botch: false,
+            tier: None,
+            margin: None,
+            degrees: None,
+            crit_successes: 0,
+            crit_failures: 0,
+        })
+    }
+}
+impl Default for Cards {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,245 @@
+use super::error::*;
+use super::locale::Locale;
+
+/// A marked part of the input, in byte offsets of the whole input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+/// A `RogError` rendered against the script that caused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub locale: Locale,
+    pub message: String,
+    /// 1-based line, 0 when the error has no location.
+    pub line: usize,
+    /// 1-based column (in characters) of the first label.
+    pub column: usize,
+    /// Text of the offending line and its byte offset in the input.
+    pub source: String,
+    pub offset: usize,
+    pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+    pub fn new(err: &RogError, input: &str, locale: Locale) -> Self {
+        let mut diagnostic = Self {
+            locale,
+            message: err.kind.message(locale),
+            line: err.line,
+            column: 0,
+            source: String::new(),
+            offset: 0,
+            labels: vec![],
+        };
+        let (Some((start, end)), Some(line)) = (err.span, err.line.checked_sub(1)) else {
+            return diagnostic;
+        };
+        let line_start: usize = input.split('\n').take(line).map(|l| l.len() + 1).sum();
+        let source = input.split('\n').nth(line).unwrap_or_default();
+        let source = source.trim_end_matches('\r');
+        let before = &source[..start.saturating_sub(line_start).min(source.len())];
+
+        let message = if err.kind == RogErr::Syntax {
+            Self::syntax_hint(&err.expected, before, locale)
+        } else {
+            String::new()
+        };
+        diagnostic.column = before.chars().count() + 1;
+        diagnostic.source = source.to_string();
+        diagnostic.offset = line_start;
+        diagnostic.labels.push(Label {
+            start,
+            end,
+            message,
+        });
+        diagnostic
+    }
+
+    /// Renders the diagnostic as plain text, with the offending line and
+    /// a `^~~~` marker under each label.
+    pub fn render(&self) -> String {
+        let header = match self.locale {
+            Locale::PtBr => format!("erro: {}", self.message),
+            Locale::En => format!("error: {}", self.message),
+        };
+        if self.labels.is_empty() {
+            return header;
+        }
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let mut lines = vec![
+            header,
+            format!("{} |", gutter),
+            format!("{} | {}", line_number, self.source),
+        ];
+        for label in &self.labels {
+            let start = label
+                .start
+                .saturating_sub(self.offset)
+                .min(self.source.len());
+            let end = label
+                .end
+                .saturating_sub(self.offset)
+                .clamp(start, self.source.len());
+            let padding = self.source[..start].chars().count();
+            let width = self.source[start..end].chars().count();
+            let marker = format!("^{}", "~".repeat(width.saturating_sub(1)));
+            let mut line = format!("{} | {}{}", gutter, " ".repeat(padding), marker);
+            if !label.message.is_empty() {
+                line = format!("{} {}", line, label.message);
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    fn syntax_hint(expected: &[String], before: &str, locale: Locale) -> String {
+        let mut names: Vec<&str> = vec![];
+        for name in expected
+            .iter()
+            .filter_map(|r| Self::describe_rule(r, locale))
+        {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let names = match names.split_last() {
+            None => String::new(),
+            Some((last, [])) => last.to_string(),
+            Some((last, rest)) => format!(
+                "{} {} {}",
+                rest.join(", "),
+                match locale {
+                    Locale::PtBr => "ou",
+                    Locale::En => "or",
+                },
+                last
+            ),
+        };
+
+        let before = before.trim_end();
+        let token = match before.chars().last() {
+            Some(last) if last.is_alphanumeric() || matches!(last, '$' | '_' | '.') => {
+                let split = before
+                    .rfind(|c: char| !(c.is_alphanumeric() || matches!(c, '$' | '_' | '.')))
+                    .map_or(0, |i| i + 1);
+                &before[split..]
+            }
+            Some(_) => {
+                let split = before
+                    .rfind(|c: char| c.is_alphanumeric() || c.is_whitespace())
+                    .map_or(0, |i| i + 1);
+                &before[split..]
+            }
+            None => "",
+        };
+
+        match (locale, token.is_empty()) {
+            (Locale::PtBr, true) => format!("esperado {} no começo da linha", names),
+            (Locale::PtBr, false) => format!("esperado {} depois de `{}`", names, token),
+            (Locale::En, true) => format!("expected {} at the start of the line", names),
+            (Locale::En, false) => format!("expected {} after `{}`", names, token),
+        }
+    }
+
+    /// Describes what a grammar rule stands for. Prefix operators are left out,
+    /// since they are always accepted alongside the value they apply to.
+    fn describe_rule(rule: &str, locale: Locale) -> Option<&'static str> {
+        Some(match (locale, rule) {
+            (_, "neg" | "not" | "ceil" | "round" | "floor" | "face_neg") => return None,
+            (Locale::PtBr, "repeat" | "repeat_literal" | "assignment" | "expression") => {
+                "uma expressão"
+            }
+            (Locale::PtBr, "integer" | "frac" | "range_bound") => "um número",
+            (Locale::PtBr, "attribute" | "attribute_literal") => "um atributo",
+            (Locale::PtBr, "dice" | "fate_dice" | "percentile_dice") => "um dado",
+            (Locale::PtBr, "cards") => "uma carta",
+            (Locale::PtBr, "deck_name") => "um baralho",
+            (Locale::PtBr, "dice_side" | "face_list" | "face_range") => "os lados do dado",
+            (Locale::PtBr, "face") => "uma face",
+            (Locale::PtBr, "fate_sign_count") => "`1` ou `2`",
+            (Locale::PtBr, "reroll_once") => "`o`",
+            (Locale::PtBr, "compare") => "uma comparação",
+            (Locale::PtBr, "group") => "`(`",
+            (Locale::PtBr, "EOI") => "o fim da linha",
+            (Locale::PtBr, "comment") => "um comentário",
+            (Locale::En, "repeat" | "repeat_literal" | "assignment" | "expression") => {
+                "an expression"
+            }
+            (Locale::En, "integer" | "frac" | "range_bound") => "a number",
+            (Locale::En, "attribute" | "attribute_literal") => "an attribute",
+            (Locale::En, "dice" | "fate_dice" | "percentile_dice") => "a die",
+            (Locale::En, "cards") => "a card",
+            (Locale::En, "deck_name") => "a deck",
+            (Locale::En, "dice_side" | "face_list" | "face_range") => "the sides of the die",
+            (Locale::En, "face") => "a face",
+            (Locale::En, "fate_sign_count") => "`1` or `2`",
+            (Locale::En, "reroll_once") => "`o`",
+            (Locale::En, "compare") => "a comparison",
+            (Locale::En, "group") => "`(`",
+            (Locale::En, "EOI") => "the end of the line",
+            (Locale::En, "comment") => "a comment",
+            (_, rule) if Self::is_dice_option(rule) => match locale {
+                Locale::PtBr => "uma opção de dado",
+                Locale::En => "a die option",
+            },
+            (_, rule) if Self::is_operator(rule) => match locale {
+                Locale::PtBr => "um operador",
+                Locale::En => "an operator",
+            },
+            // A rule no message knows of yet, better left out than mislabeled.
+            _ => return None,
+        })
+    }
+
+    fn is_dice_option(rule: &str) -> bool {
+        matches!(
+            rule,
+            "explode"
+                | "compound"
+                | "penetrate"
+                | "explode_depth"
+                | "reroll"
+                | "keep_drop"
+                | "crit_success"
+                | "crit_fail"
+                | "success"
+                | "failure"
+                | "unique"
+                | "clamp_min"
+                | "clamp_max"
+                | "aro"
+                | "sort"
+                | "fate_signs"
+                | "bonus"
+                | "penalty"
+        )
+    }
+
+    fn is_operator(rule: &str) -> bool {
+        matches!(
+            rule,
+            "percent"
+                | "hyper_add"
+                | "hyper_sub"
+                | "add"
+                | "sub"
+                | "mul"
+                | "div"
+                | "counter_less"
+                | "counter_greater"
+                | "less_eq"
+                | "less"
+                | "greater_eq"
+                | "greater"
+                | "eq"
+                | "or"
+                | "and"
+        ) || rule.starts_with("assign_")
+    }
+}
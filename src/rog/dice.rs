@@ -1,62 +1,321 @@
 use std::vec;
 
+use super::error::*;
 use super::rogcons::*;
-use rand::random;
+use super::roller::*;
 use std::result::Result;
 
-pub enum DiceExplosion {
-    NoExplode,
-    Explode(usize),
-    Default,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplodeKind {
+    /// `!`, every explosion is a die of its own.
+    Explode,
+    /// `!!`, explosions are added to the die that triggered them.
+    Compound,
+    /// `!p`, like `!!`, but every explosion is worth 1 less than rolled.
+    Penetrate,
+}
+impl ExplodeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ExplodeKind::Explode => "!",
+            ExplodeKind::Compound => "!!",
+            ExplodeKind::Penetrate => "!p",
+        }
+    }
+    /// Value of a die made of `draws`.
+    fn die_value(self, draws: &[(f64, RollKind)]) -> f64 {
+        let sum: f64 = draws.iter().map(|(v, _)| v).sum();
+        match self {
+            ExplodeKind::Penetrate => sum - (draws.len() - 1) as f64,
+            _ => sum,
+        }
+    }
 }
 
-pub struct RollConfig {
-    pub keep_drop: Option<(KeepDrop, usize)>,
-    pub explode: DiceExplosion,
-    pub aro: bool,
-    pub sort: bool,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
 }
-impl RollConfig {
-    pub fn new() -> Self {
-        Self {
-            keep_drop: None,
-            explode: DiceExplosion::NoExplode,
-            aro: false,
-            sort: false,
+impl CompareOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Less => "<",
+            CompareOp::LessEq => "<=",
+            CompareOp::Greater => ">",
+            CompareOp::GreaterEq => ">=",
         }
     }
 }
-impl ToString for RollConfig {
-    fn to_string(&self) -> String {
-        format!(
-            "{}{}{}{}",
-            match self.explode {
-                DiceExplosion::NoExplode => String::new(),
-                DiceExplosion::Default => String::from("!"),
-                DiceExplosion::Explode(x) => format!("!{}", x),
-            },
-            if let Some(kd) = &self.keep_drop {
-                format!(
-                    "{}{}",
-                    match kd.0 {
-                        KeepDrop::Crit => "c",
-                        KeepDrop::DropHigh => "dh",
-                        KeepDrop::DropLow => "d",
-                        KeepDrop::KeepHigh => "k",
-                        KeepDrop::KeepLow => "kl",
-                    },
-                    kd.1
-                )
-            } else {
-                String::new()
+
+/// A compare point such as `>=5`, selecting some faces of a die.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compare<N = usize> {
+    pub op: CompareOp,
+    pub value: N,
+}
+impl<N> Compare<N> {
+    pub fn try_map<M, E>(&self, f: impl FnOnce(&N) -> Result<M, E>) -> Result<Compare<M>, E> {
+        Ok(Compare {
+            op: self.op,
+            value: f(&self.value)?,
+        })
+    }
+}
+impl Compare {
+    pub fn as_f64(&self) -> Compare<f64> {
+        Compare {
+            op: self.op,
+            value: self.value as f64,
+        }
+    }
+}
+impl Compare<f64> {
+    pub fn matches(&self, face: f64) -> bool {
+        let value = self.value;
+        match self.op {
+            CompareOp::Eq => face == value,
+            CompareOp::Less => face < value,
+            CompareOp::LessEq => face <= value,
+            CompareOp::Greater => face > value,
+            CompareOp::GreaterEq => face >= value,
+        }
+    }
+    /// How many faces of a die with `sides` sides, numbered from 1, match.
+    pub fn faces(&self, sides: usize) -> usize {
+        let sides = sides as f64;
+        let below = |value: f64| value.clamp(0., sides);
+        let count = match self.op {
+            CompareOp::Eq => {
+                (self.value.fract() == 0. && (1. ..=sides).contains(&self.value)) as u8 as f64
+            }
+            CompareOp::Less => below(self.value.ceil() - 1.),
+            CompareOp::LessEq => below(self.value.floor()),
+            CompareOp::Greater => sides - below(self.value.floor()),
+            CompareOp::GreaterEq => sides - below(self.value.ceil() - 1.),
+        };
+        count as usize
+    }
+}
+impl<N: std::fmt::Display> std::fmt::Display for Compare<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.op.as_str(), self.value)
+    }
+}
+
+/// `>=8f1` counts the dice matching the first compare point as successes and
+/// those matching the failure point as failures. `f1` is the same as `f=1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Success<N = usize> {
+    pub compare: Compare<N>,
+    pub failure: Option<Compare<N>>,
+}
+
+/// `r<cmp>` rerolls a die until it stops matching, `ro<cmp>` rerolls it once.
+/// `r1` is the same as `r=1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reroll<N = usize> {
+    pub once: bool,
+    pub compare: Compare<N>,
+}
+
+/// A single option of a dice roll. Options are generic over how their numbers
+/// are written, so that `N` may stay unresolved (e.g. an attribute) until the
+/// roll happens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Modifier<N = usize> {
+    /// `!`, `!!` or `!p`, exploding on the faces matching the compare point
+    /// (`!5` being `!>=5`) or on the highest face, at most `depth` times a die.
+    Explode {
+        kind: ExplodeKind,
+        compare: Option<Compare<N>>,
+        depth: Option<N>,
+    },
+    Reroll(Reroll<N>),
+    /// `u`, no face comes up twice in the roll.
+    Unique,
+    /// `min3`, dice below it count as it.
+    Min(N),
+    /// `max5`, dice above it count as it.
+    Max(N),
+    KeepDrop(KeepDrop, N),
+    /// `cs20`, dice matching it are critical successes, `cs19` being `cs>=19`.
+    CritSuccess(Compare<N>),
+    /// `cf1`, dice matching it are critical failures, `cf2` being `cf<=2`.
+    CritFail(Compare<N>),
+    Success(Success<N>),
+    Aro,
+    Sort,
+}
+impl<N> Modifier<N> {
+    pub fn numbers(&self) -> Vec<&N> {
+        match self {
+            Modifier::Explode { compare, depth, .. } => {
+                let compare = compare.iter().map(|compare| &compare.value);
+                compare.chain(depth).collect()
+            }
+            Modifier::Reroll(reroll) => vec![&reroll.compare.value],
+            Modifier::CritSuccess(compare) | Modifier::CritFail(compare) => vec![&compare.value],
+            Modifier::Min(value) | Modifier::Max(value) | Modifier::KeepDrop(_, value) => {
+                vec![value]
+            }
+            Modifier::Success(success) => {
+                let failure = success.failure.iter().map(|failure| &failure.value);
+                std::iter::once(&success.compare.value)
+                    .chain(failure)
+                    .collect()
+            }
+            Modifier::Unique | Modifier::Aro | Modifier::Sort => vec![],
+        }
+    }
+    pub fn try_map<M, E>(&self, mut f: impl FnMut(&N) -> Result<M, E>) -> Result<Modifier<M>, E> {
+        Ok(match self {
+            Modifier::Explode {
+                kind,
+                compare,
+                depth,
+            } => Modifier::Explode {
+                kind: *kind,
+                compare: match compare {
+                    Some(compare) => Some(compare.try_map(&mut f)?),
+                    None => None,
+                },
+                depth: match depth {
+                    Some(depth) => Some(f(depth)?),
+                    None => None,
+                },
             },
-            if self.aro { "aro" } else { "" },
-            if self.sort { "s" } else { "" }
-        )
+            Modifier::Reroll(reroll) => Modifier::Reroll(Reroll {
+                once: reroll.once,
+                compare: reroll.compare.try_map(&mut f)?,
+            }),
+            Modifier::Unique => Modifier::Unique,
+            Modifier::Min(min) => Modifier::Min(f(min)?),
+            Modifier::Max(max) => Modifier::Max(f(max)?),
+            Modifier::KeepDrop(keep_drop, value) => Modifier::KeepDrop(*keep_drop, f(value)?),
+            Modifier::CritSuccess(compare) => Modifier::CritSuccess(compare.try_map(&mut f)?),
+            Modifier::CritFail(compare) => Modifier::CritFail(compare.try_map(&mut f)?),
+            Modifier::Success(success) => Modifier::Success(Success {
+                compare: success.compare.try_map(&mut f)?,
+                failure: match &success.failure {
+                    Some(failure) => Some(failure.try_map(&mut f)?),
+                    None => None,
+                },
+            }),
+            Modifier::Aro => Modifier::Aro,
+            Modifier::Sort => Modifier::Sort,
+        })
+    }
+}
+impl<N: std::fmt::Display> std::fmt::Display for Modifier<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Modifier::Explode {
+                kind,
+                compare,
+                depth,
+            } => {
+                write!(f, "{}", kind.as_str())?;
+                match compare {
+                    Some(x) if x.op == CompareOp::GreaterEq => write!(f, "{}", x.value)?,
+                    Some(x) => write!(f, "{}", x)?,
+                    None => {}
+                }
+                match depth {
+                    Some(depth) => write!(f, "{{{}}}", depth),
+                    None => Ok(()),
+                }
+            }
+            Modifier::Reroll(reroll) => {
+                write!(f, "{}", if reroll.once { "ro" } else { "r" })?;
+                match reroll.compare.op {
+                    CompareOp::Eq => write!(f, "{}", reroll.compare.value),
+                    _ => write!(f, "{}", reroll.compare),
+                }
+            }
+            Modifier::Unique => write!(f, "u"),
+            Modifier::Min(min) => write!(f, "min{}", min),
+            Modifier::Max(max) => write!(f, "max{}", max),
+            Modifier::KeepDrop(keep_drop, value) => write!(
+                f,
+                "{}{}",
+                match keep_drop {
+                    KeepDrop::Crit => "c",
+                    KeepDrop::DropHigh => "dh",
+                    KeepDrop::DropLow => "d",
+                    KeepDrop::KeepHigh => "k",
+                    KeepDrop::KeepLow => "kl",
+                },
+                value
+            ),
+            Modifier::CritSuccess(x) if x.op == CompareOp::GreaterEq => write!(f, "cs{}", x.value),
+            Modifier::CritSuccess(x) => write!(f, "cs{}", x),
+            Modifier::CritFail(x) if x.op == CompareOp::LessEq => write!(f, "cf{}", x.value),
+            Modifier::CritFail(x) => write!(f, "cf{}", x),
+            Modifier::Success(success) => {
+                write!(f, "{}", success.compare)?;
+                match &success.failure {
+                    Some(failure) if failure.op == CompareOp::Eq => write!(f, "f{}", failure.value),
+                    Some(failure) => write!(f, "f{}", failure),
+                    None => Ok(()),
+                }
+            }
+            Modifier::Aro => write!(f, "aro"),
+            Modifier::Sort => write!(f, "s"),
+        }
     }
 }
 
-#[derive(PartialEq, Eq)]
+/// The options of a dice roll, in the order they were written. Whatever that
+/// order, they apply in fixed phases: each die is drawn (without repeating
+/// faces with `u`), rerolled and exploded, then clamped by `min` and `max`.
+/// `aro` then adds dice while they are all the same, keep/drop options pick
+/// the dice kept, and successes and crits are counted on those.
+///
+/// Only within a phase does the written order matter: `min` and `max` clamp
+/// one after the other, and keep/drop options apply one after the other to
+/// the dice still kept, so `8d6d2k3` drops the 2 lowest dice and keeps the 3
+/// highest of those left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollConfig<N = usize> {
+    pub modifiers: Vec<Modifier<N>>,
+}
+impl<N> RollConfig<N> {
+    pub fn new() -> Self {
+        Self { modifiers: vec![] }
+    }
+    pub fn numbers(&self) -> Vec<&N> {
+        self.modifiers.iter().flat_map(Modifier::numbers).collect()
+    }
+    /// Converts every number of the config with `f`, stopping at the first error.
+    pub fn try_map<M, E>(&self, mut f: impl FnMut(&N) -> Result<M, E>) -> Result<RollConfig<M>, E> {
+        let modifiers = self
+            .modifiers
+            .iter()
+            .map(|modifier| modifier.try_map(&mut f));
+        Ok(RollConfig {
+            modifiers: modifiers.collect::<Result<_, _>>()?,
+        })
+    }
+}
+impl<N> Default for RollConfig<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<N: std::fmt::Display> std::fmt::Display for RollConfig<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.modifiers
+            .iter()
+            .try_for_each(|modifier| write!(f, "{}", modifier))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeepDrop {
     KeepHigh,
     KeepLow,
@@ -65,128 +324,497 @@ pub enum KeepDrop {
     Crit,
 }
 
+/// The faces a die may land on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Faces {
+    /// `d6`, faces from 1 to the number of sides.
+    Sides(usize),
+    /// `d{0,0,1,1,2,3}`, or a named face set.
+    List(Vec<f64>),
+    /// `d[-3..3]`, every whole number between the bounds.
+    Range(i64, i64),
+    /// `df`, a Fate die with a `-`, a blank and a `+`, or `df.1` and `df.2`,
+    /// six-sided Fudge dice with that many `-` and `+` faces each.
+    Fate(Option<usize>),
+}
+impl Faces {
+    pub fn len(&self) -> usize {
+        match self {
+            Faces::Sides(sides) => *sides,
+            Faces::List(faces) => faces.len(),
+            Faces::Range(low, high) => (*high as i128 - *low as i128 + 1).max(0) as usize,
+            Faces::Fate(None) => 3,
+            Faces::Fate(Some(_)) => 6,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Value of the face at `index`, counting from 1.
+    fn face(&self, index: usize) -> f64 {
+        match self {
+            Faces::Sides(_) => index as f64,
+            Faces::List(faces) => faces[index - 1],
+            Faces::Range(low, _) => (*low as i128 + index as i128 - 1) as f64,
+            Faces::Fate(None) => index as f64 - 2.,
+            Faces::Fate(Some(signs)) if index <= *signs => -1.,
+            Faces::Fate(Some(signs)) if index > 6 - signs => 1.,
+            Faces::Fate(Some(_)) => 0.,
+        }
+    }
+    /// How a face is written, `+` and `-` on Fate dice.
+    fn label(&self, value: f64) -> String {
+        match self {
+            Faces::Fate(_) if value == 1. => "+".to_string(),
+            Faces::Fate(_) if value == -1. => "-".to_string(),
+            _ => value.to_string(),
+        }
+    }
+    fn lowest(&self) -> f64 {
+        match self {
+            Faces::List(faces) => faces.iter().copied().fold(f64::INFINITY, f64::min),
+            _ => self.face(1),
+        }
+    }
+    fn highest(&self) -> f64 {
+        match self {
+            Faces::List(faces) => faces.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            _ => self.face(self.len()),
+        }
+    }
+    /// How many faces match `compare`.
+    fn matching(&self, compare: &Compare<f64>) -> usize {
+        match self {
+            Faces::Sides(sides) => compare.faces(*sides),
+            Faces::List(faces) => faces.iter().filter(|f| compare.matches(**f)).count(),
+            Faces::Range(low, _) => {
+                let shifted = Compare {
+                    op: compare.op,
+                    value: compare.value - (*low as i128 - 1) as f64,
+                };
+                shifted.faces(self.len())
+            }
+            Faces::Fate(_) => (1..=self.len())
+                .filter(|index| compare.matches(self.face(*index)))
+                .count(),
+        }
+    }
+}
+impl std::fmt::Display for Faces {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Faces::Sides(sides) => write!(f, "{}", sides),
+            Faces::List(faces) => {
+                let faces: Vec<String> = faces.iter().map(|face| face.to_string()).collect();
+                write!(f, "{{{}}}", faces.join(","))
+            }
+            Faces::Range(low, high) => write!(f, "[{}..{}]", low, high),
+            Faces::Fate(None) => write!(f, "f"),
+            Faces::Fate(Some(signs)) => write!(f, "f.{}", signs),
+        }
+    }
+}
+
+/// A die of a roll: the draws adding up to its value, and the draws it replaced
+/// because of a reroll option.
+struct Die {
+    draws: Vec<(f64, RollKind)>,
+    rerolled: Vec<(f64, RollKind)>,
+}
+
+/// Where the dice of a roll draw their faces from. Unique rolls never draw
+/// the same face twice.
+struct FacePool<'a> {
+    faces: &'a Faces,
+    /// Indexes of the faces drawn so far, sorted, when the roll is unique.
+    drawn: Option<Vec<usize>>,
+}
+impl FacePool<'_> {
+    fn draw(&mut self, roller: &mut Roller) -> Result<f64, RogErr> {
+        let sides = self.faces.len();
+        let Some(drawn) = &mut self.drawn else {
+            return Ok(match self.faces {
+                Faces::Fate(None) => roller.fate() as f64,
+                faces => faces.face(roller.die(sides)),
+            });
+        };
+        if drawn.len() >= sides {
+            return Err(RogErr::DrawMax(sides));
+        }
+        // The nth face that wasn't drawn yet.
+        let mut index = roller.die(sides - drawn.len());
+        for face in drawn.iter() {
+            if *face <= index {
+                index += 1;
+            }
+        }
+        let position = drawn.partition_point(|face| *face < index);
+        drawn.insert(position, index);
+        Ok(self.faces.face(index))
+    }
+}
+
+/// How the dice of a roll explode, resolved from its `RollConfig`.
+struct Explosion {
+    compare: Option<Compare<f64>>,
+    kind: ExplodeKind,
+    depth: Option<usize>,
+}
+
 pub struct Dice {
     pub count: usize,
-    pub sides: usize,
+    pub faces: Faces,
     pub config: RollConfig,
 }
 impl Dice {
     pub fn new() -> Self {
         Self {
             count: 1,
-            sides: 6,
+            faces: Faces::Sides(6),
             config: RollConfig::new(),
         }
     }
-    fn die_formatter(value: f64, crit: f64, kept: bool) -> String {
-        let mut result = format!("{:.0}", value);
-        if value == 1. || value >= crit {
-            result = format!("**{}**", result);
+    /// Formats a die from its draws, showing how a compounded or penetrating
+    /// die added up, next to the rerolled draws it replaced. A die clamped by
+    /// `min` or `max` shows what it rolled before its `value`.
+    /// `mark` is the markdown the value is wrapped in, if any.
+    fn die_formatter(
+        die: &Die,
+        faces: &Faces,
+        explode_kind: ExplodeKind,
+        value: f64,
+        mark: Option<&str>,
+        kept: bool,
+    ) -> String {
+        let draws = &die.draws;
+        let rolled = explode_kind.die_value(draws);
+        let mut result = faces.label(value);
+        if let Some(mark) = mark {
+            result = format!("{}{}{}", mark, result, mark);
+        }
+        if rolled != value {
+            result = format!("{}→{}", faces.label(rolled), result);
+        }
+        if draws.len() > 1 {
+            let penalty = match explode_kind {
+                ExplodeKind::Penetrate => "-1",
+                _ => "",
+            };
+            let parts: Vec<String> = draws
+                .iter()
+                .enumerate()
+                .map(|(i, (v, _))| match i {
+                    0 => faces.label(*v),
+                    _ => format!("{}{}", faces.label(*v), penalty),
+                })
+                .collect();
+            result = format!("{}({})", result, parts.join("+"));
         }
         if !kept {
             result = format!("~~{}~~", result);
         }
+        for (value, _) in die.rerolled.iter().rev() {
+            result = format!("~~{}~~ {}", faces.label(*value), result);
+        }
         result
     }
-    fn single_roll(sides: usize, explode_size: Option<usize>) -> Vec<f64> {
-        let mut values = vec![];
-        let mut value: f64;
+    fn single_roll(
+        roller: &mut Roller,
+        pool: &mut FacePool,
+        rerolls: &[Reroll<f64>],
+        explosion: &Explosion,
+        kind: RollKind,
+    ) -> Result<Die, RogErr> {
+        let mut rerolled = vec![];
+        let mut value = pool.draw(roller)?;
+        let mut kind = kind;
+        // Which of the `ro` options already rerolled this die.
+        let mut used = vec![false; rerolls.len()];
+        while let Some(i) =
+            (0..rerolls.len()).find(|i| !used[*i] && rerolls[*i].compare.matches(value))
+        {
+            if rerolled.len() >= MAX_REROLLS {
+                return Err(RogErr::RerollMax);
+            }
+            used[i] = rerolls[i].once;
+            rerolled.push((value, kind));
+            value = pool.draw(roller)?;
+            kind = RollKind::Reroll;
+        }
+
+        let mut values = vec![(value, kind)];
         loop {
-            value = (random::<usize>() % sides + 1) as f64;
-            values.push(value);
-            if let Some(explode) = explode_size {
-                if value < explode as f64 {
-                    break;
+            match &explosion.compare {
+                Some(compare) if compare.matches(value) => {}
+                _ => break,
+            }
+            if let Some(depth) = explosion.depth {
+                if values.len() > depth {
+                    return Err(RogErr::ExplodeDepth(depth));
                 }
-            } else {
-                break;
             }
+            value = pool.draw(roller)?;
+            values.push((value, RollKind::Explosion));
         }
-        values
+        Ok(Die {
+            draws: values,
+            rerolled,
+        })
+    }
+    /// Rolls a die, returning each resulting die: a single one holding every
+    /// draw when compounding or penetrating, or one per draw otherwise.
+    fn roll_die(
+        roller: &mut Roller,
+        pool: &mut FacePool,
+        rerolls: &[Reroll<f64>],
+        explosion: &Explosion,
+        kind: RollKind,
+    ) -> Result<Vec<Die>, RogErr> {
+        let die = Self::single_roll(roller, pool, rerolls, explosion, kind)?;
+        Ok(match explosion.kind {
+            ExplodeKind::Explode => {
+                let mut rerolled = Some(die.rerolled);
+                die.draws
+                    .into_iter()
+                    .map(|draw| Die {
+                        draws: vec![draw],
+                        rerolled: rerolled.take().unwrap_or_default(),
+                    })
+                    .collect()
+            }
+            ExplodeKind::Compound | ExplodeKind::Penetrate => vec![die],
+        })
     }
-    pub fn roll(self) -> Result<RogCons, RogErr> {
-        if self.count > 100 {
-            return Err(RogErr::CountMax);
+    pub fn roll(self, roller: &mut Roller) -> Result<RogCons, RogErr> {
+        if self.count > MAX_DICE {
+            return Err(RogErr::CountMax(self.count));
         }
-        if self.sides < 2 {
-            return Err(RogErr::CountMin);
+        let faces = &self.faces;
+        let sides = faces.len();
+        if sides < 2 {
+            return Err(RogErr::CountMin(sides));
         }
-        let sort = self.config.sort || self.config.keep_drop.is_some();
-        let explode_size = match self.config.explode {
-            DiceExplosion::Default => Some(self.sides),
-            DiceExplosion::Explode(ex) => Some(ex),
-            DiceExplosion::NoExplode => None,
+        let mut sort = false;
+        let mut explosion = Explosion {
+            compare: None,
+            kind: ExplodeKind::Explode,
+            depth: None,
         };
-
-        if let Some(size) = explode_size {
-            if size < 2 {
-                return Err(RogErr::ExplodeMin);
-            }
-            if size < self.sides / 100 {
-                return Err(RogErr::ExplodeChanceHigh);
+        let mut rerolls = vec![];
+        let (mut unique, mut aro, mut success) = (false, false, None);
+        // Natural crits, on the highest and lowest faces, unless set otherwise.
+        let mut crit_success = Compare {
+            op: CompareOp::GreaterEq,
+            value: faces.highest(),
+        };
+        let mut crit_fail = Compare {
+            op: CompareOp::LessEq,
+            value: faces.lowest(),
+        };
+        // A later crit option replaces an earlier one, but every reroll
+        // option is kept. Explosion and success are only written once.
+        for modifier in &self.config.modifiers {
+            match modifier {
+                Modifier::Explode {
+                    kind,
+                    compare,
+                    depth,
+                } => {
+                    let compare = match compare {
+                        // Only dice numbered from 1 are sure to always explode below 2.
+                        Some(compare)
+                            if matches!(faces, Faces::Sides(_))
+                                && compare.op == CompareOp::GreaterEq
+                                && compare.value < 2 =>
+                        {
+                            return Err(RogErr::ExplodeMin(compare.value));
+                        }
+                        Some(compare) => compare.as_f64(),
+                        None => Compare {
+                            op: CompareOp::GreaterEq,
+                            value: faces.highest(),
+                        },
+                    };
+                    let misses = sides - faces.matching(&compare);
+                    if misses == 0 || misses.saturating_add(1) < sides / 100 {
+                        return Err(RogErr::ExplodeChanceHigh(compare, sides));
+                    }
+                    explosion = Explosion {
+                        compare: Some(compare),
+                        kind: *kind,
+                        depth: *depth,
+                    };
+                }
+                Modifier::Reroll(reroll) => {
+                    let compare = reroll.compare.as_f64();
+                    if !reroll.once && faces.matching(&compare) == sides {
+                        return Err(RogErr::RerollAll(sides));
+                    }
+                    rerolls.push(Reroll {
+                        once: reroll.once,
+                        compare,
+                    });
+                }
+                Modifier::Unique => unique = true,
+                Modifier::Aro => aro = true,
+                Modifier::Success(found) => success = Some(found),
+                Modifier::KeepDrop(KeepDrop::Crit, value) => {
+                    sort = true;
+                    crit_success = Compare {
+                        op: CompareOp::GreaterEq,
+                        value: *value as f64,
+                    };
+                }
+                Modifier::Sort | Modifier::KeepDrop(..) => sort = true,
+                Modifier::CritSuccess(compare) => crit_success = compare.as_f64(),
+                Modifier::CritFail(compare) => crit_fail = compare.as_f64(),
+                Modifier::Min(_) | Modifier::Max(_) => {}
             }
         }
+        let explode_kind = explosion.kind;
 
-        let mut values: Vec<f64> = vec![];
+        let mut pool = FacePool {
+            faces,
+            drawn: unique.then(Vec::new),
+        };
+        // Every die of the roll, explosions included when they aren't compounded.
+        let mut rolls: Vec<Die> = vec![];
         for _ in 0..self.count {
-            values.append(&mut Self::single_roll(self.sides, explode_size));
+            rolls.append(&mut Self::roll_die(
+                roller,
+                &mut pool,
+                &rerolls,
+                &explosion,
+                RollKind::Roll,
+            )?);
         }
+        // `min` and `max` are applied in the order they were written.
+        let die_value = |die: &Die| {
+            let value = explode_kind.die_value(&die.draws);
+            self.config
+                .modifiers
+                .iter()
+                .fold(value, |value, modifier| match modifier {
+                    Modifier::Min(min) => value.max(*min as f64),
+                    Modifier::Max(max) => value.min(*max as f64),
+                    _ => value,
+                })
+        };
 
-        if self.config.aro && values.len() > 1 {
-            let first = values[0];
-            let mut all_same = values.iter().all(|v| v == &first);
+        if aro && rolls.len() > 1 {
+            let first = die_value(&rolls[0]);
+            let mut all_same = rolls.iter().all(|die| die_value(die) == first);
             while all_same {
-                let mut new_values = Self::single_roll(self.sides, explode_size);
-                all_same = new_values.iter().all(|v| v == &first);
-                values.append(&mut new_values);
+                // Faces that are all the same, or clamped to the same value,
+                // would never stop coming out the same.
+                if rolls.len() >= MAX_DICE {
+                    return Err(RogErr::AroMax);
+                }
+                let mut new_rolls =
+                    Self::roll_die(roller, &mut pool, &rerolls, &explosion, RollKind::Aro)?;
+                all_same = new_rolls.iter().all(|die| die_value(die) == first);
+                rolls.append(&mut new_rolls);
             }
         }
+        let values: Vec<f64> = rolls.iter().map(die_value).collect();
 
-        let values: Vec<(f64, bool)> = values.into_iter().map(|x| (x, true)).collect();
-        let mut sorted_values: Vec<(f64, bool)> = values.clone().into_iter().collect();
+        // Indexes into `values`, from the highest value to the lowest.
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|a, b| {
+            values[*b]
+                .partial_cmp(&values[*a])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let mut kept = vec![true; values.len()];
 
-        sorted_values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        sorted_values.reverse();
+        for modifier in &self.config.modifiers {
+            let Modifier::KeepDrop(keep_drop, value) = modifier else {
+                continue;
+            };
+            // The dice still kept, from the highest value to the lowest.
+            let remaining: Vec<usize> = order.iter().copied().filter(|i| kept[*i]).collect();
+            let keep_value = (*value).min(self.count).min(remaining.len());
+            let dropped = match keep_drop {
+                KeepDrop::KeepHigh => &remaining[keep_value..],
+                KeepDrop::KeepLow => &remaining[..remaining.len() - keep_value],
+                KeepDrop::DropHigh => &remaining[..keep_value],
+                KeepDrop::DropLow => &remaining[remaining.len() - keep_value..],
+                KeepDrop::Crit => &[],
+            };
+            dropped.iter().for_each(|index| kept[*index] = false);
+        }
 
-        let mut keep_range = 0..sorted_values.len();
-        let mut crit_value = self.sides as f64;
-        if let Some((keep_drop, keep_value)) = &self.config.keep_drop {
-            let keep_value = usize::clamp(*keep_value, 0, self.count);
-            match keep_drop {
-                KeepDrop::KeepHigh => keep_range.end = keep_value,
-                KeepDrop::KeepLow => keep_range.start = sorted_values.len() - keep_value,
-                KeepDrop::DropHigh => keep_range.start = keep_value,
-                KeepDrop::DropLow => keep_range.end = sorted_values.len() - keep_value,
-                KeepDrop::Crit => crit_value = keep_value as f64,
-            }
-            sorted_values.iter_mut().enumerate().for_each(|(i, value)| {
-                if !keep_range.contains(&i) {
-                    *value = (value.0, false)
-                }
-            });
+        if !sort {
+            order.sort();
         }
+        let mut accumulated_value = order.iter().filter(|i| kept[**i]).map(|i| values[*i]).sum();
 
-        let final_values = if sort { sorted_values } else { values };
-        let accumulated_value = final_values
-            .iter()
-            .filter(|(_, kept)| *kept)
-            .map(|(x, _)| x)
-            .sum();
+        let (mut glitch, mut botch) = (false, false);
+        let success = success.map(|success| Success {
+            compare: success.compare.as_f64(),
+            failure: success.failure.as_ref().map(Compare::as_f64),
+        });
+        let kept_values = || {
+            values
+                .iter()
+                .zip(&kept)
+                .filter(|(_, k)| **k)
+                .map(|(v, _)| *v)
+        };
+        if let Some(success) = &success {
+            let successes = kept_values()
+                .filter(|v| success.compare.matches(*v))
+                .count();
+            let failures = kept_values()
+                .filter(|v| !success.compare.matches(*v))
+                .filter(|v| success.failure.as_ref().is_some_and(|f| f.matches(*v)))
+                .count();
+            accumulated_value = successes as f64 - failures as f64;
+            glitch = failures * 2 > kept_values().count();
+            botch = successes == 0 && failures > 0;
+        }
+        let crit_successes = kept_values().filter(|v| crit_success.matches(*v)).count();
+        let crit_failures = kept_values().filter(|v| crit_fail.matches(*v)).count();
+        let mark = |value: f64| match &success {
+            Some(success) if success.compare.matches(value) => Some("**"),
+            Some(success) if success.failure.as_ref().is_some_and(|f| f.matches(value)) => {
+                Some("_")
+            }
+            Some(_) => None,
+            None if crit_success.matches(value) || crit_fail.matches(value) => Some("**"),
+            None => None,
+        };
 
-        let mut text = final_values
+        let mut text = order
             .iter()
-            .map(|(v, keep)| Self::die_formatter(*v, crit_value, *keep))
+            .map(|i| {
+                Self::die_formatter(
+                    &rolls[*i],
+                    faces,
+                    explode_kind,
+                    values[*i],
+                    mark(values[*i]),
+                    kept[*i],
+                )
+            })
             .collect::<Vec<_>>()
             .join(", ");
-        text = format!(
-            "[{}] {}d{}{}",
-            text,
-            self.count,
-            self.sides,
-            self.config.to_string()
-        );
+        let notation = format!("{}d{}{}", self.count, faces, self.config);
+        text = format!("[{}] {}", text, notation);
 
-        let final_values: Vec<f64> = final_values.into_iter().map(|(v, _)| v).collect();
+        roller.record(
+            &notation,
+            sides,
+            rolls.iter().zip(&kept).flat_map(|(die, kept)| {
+                let rerolled = die
+                    .rerolled
+                    .iter()
+                    .map(|(value, kind)| (*value, *kind, false));
+                let draws = die.draws.iter().map(|(value, kind)| (*value, *kind, *kept));
+                rerolled.chain(draws)
+            }),
+        );
+        let final_values: Vec<f64> = order.into_iter().map(|i| values[i]).collect();
 
         Ok(RogCons {
             value: accumulated_value,
@@ -194,43 +822,185 @@ impl Dice {
             values: final_values,
             boolean: false,
             dice: self.count as u32,
+            glitch,
+            botch,
+            tier: None,
+            margin: None,
+            degrees: None,
+            crit_successes: crit_successes as u32,
+            crit_failures: crit_failures as u32,
         })
     }
 }
 
+impl Default for Dice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `Xdf`, Fate dice, rolled as `Dice` with Fate faces so that they take
+/// the same options.
 pub struct FateDice {
     pub count: usize,
+    /// `-` and `+` faces of a six-sided Fudge die, `df.1` or `df.2`.
+    pub signs: Option<usize>,
+    pub config: RollConfig,
+}
+impl Default for FateDice {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FateDice {
     pub fn new() -> Self {
-        Self { count: 1 }
-    }
-    fn format_dice(x: &f64) -> String {
-        if x == &1. {
-            "**+**".to_string()
-        } else if x == &0. {
-            "0".to_string()
-        } else if x == &-1. {
-            "**-**".to_string()
-        } else {
-            "???".to_string()
+        Self {
+            count: 1,
+            signs: None,
+            config: RollConfig::new(),
+        }
+    }
+    /// Name of `value` on the Fate ladder, if it's a whole number.
+    pub fn ladder(value: f64) -> Option<&'static str> {
+        if value.fract() != 0. {
+            return None;
+        }
+        Some(match value as i64 {
+            i64::MIN..=-2 => "Terrível",
+            -1 => "Ruim",
+            0 => "Medíocre",
+            1 => "Regular",
+            2 => "Razoável",
+            3 => "Bom",
+            4 => "Ótimo",
+            5 => "Excepcional",
+            6 => "Fantástico",
+            7 => "Épico",
+            _ => "Lendário",
+        })
+    }
+    pub fn roll(self, roller: &mut Roller) -> Result<RogCons, RogErr> {
+        let dice = Dice {
+            count: self.count,
+            faces: Faces::Fate(self.signs),
+            config: self.config,
+        };
+        dice.roll(roller)
+    }
+}
+
+/// `d%`, rolled as a tens die and a units die, where 00 and 0 make 100.
+/// Bonus dice roll extra tens dice and keep the lowest result, penalty
+/// dice keep the highest, and each bonus die cancels a penalty die.
+pub struct PercentileDice {
+    pub count: usize,
+    pub bonus: usize,
+    pub penalty: usize,
+}
+impl Default for PercentileDice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The tens dice of a percentile die, of which only `kept` counts,
+/// and its units die.
+struct PercentileRoll {
+    tens: Vec<f64>,
+    kept: usize,
+    units: f64,
+}
+
+impl PercentileDice {
+    pub fn new() -> Self {
+        Self {
+            count: 1,
+            bonus: 0,
+            penalty: 0,
         }
     }
-    pub fn roll(self) -> Result<RogCons, RogErr> {
-        if self.count > 100 {
-            return Err(RogErr::CountMax);
+    /// Value of a tens and a units die, both rolled from 0.
+    fn value(tens: f64, units: f64) -> f64 {
+        match tens + units {
+            0. => 100.,
+            value => value,
         }
-        let values: Vec<f64> = (0..self.count)
-            .map(|_| (random::<u32>() % 3) as f64 - 1.)
+    }
+    fn format_dice(roll: &PercentileRoll) -> String {
+        let value = Self::value(roll.tens[roll.kept], roll.units);
+        let mut result = format!("{}", value);
+        if value == 1. || value == 100. {
+            result = format!("**{}**", result);
+        }
+        let tens: Vec<String> = roll
+            .tens
+            .iter()
+            .enumerate()
+            .map(|(i, tens)| match i == roll.kept {
+                true => format!("{:02}", tens),
+                false => format!("~~{:02}~~", tens),
+            })
+            .collect();
+        format!("{}({}+{})", result, tens.join("/"), roll.units)
+    }
+    pub fn roll(self, roller: &mut Roller) -> Result<RogCons, RogErr> {
+        if self.count > MAX_DICE {
+            return Err(RogErr::CountMax(self.count));
+        }
+        let extra = self.bonus.abs_diff(self.penalty);
+        if extra > MAX_DICE {
+            return Err(RogErr::CountMax(extra));
+        }
+        let rolls: Vec<PercentileRoll> = (0..self.count)
+            .map(|_| {
+                let tens: Vec<f64> = (0..=extra)
+                    .map(|_| ((roller.die(10) - 1) * 10) as f64)
+                    .collect();
+                let units = (roller.die(10) - 1) as f64;
+                let order = |a: &&f64, b: &&f64| {
+                    let (a, b) = (Self::value(**a, units), Self::value(**b, units));
+                    a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+                };
+                let kept = match self.bonus > self.penalty {
+                    true => tens.iter().enumerate().min_by(|a, b| order(&a.1, &b.1)),
+                    false => tens.iter().enumerate().max_by(|a, b| order(&a.1, &b.1)),
+                };
+                let kept = kept.map_or(0, |(i, _)| i);
+                PercentileRoll { tens, kept, units }
+            })
+            .collect();
+        let values: Vec<f64> = rolls
+            .iter()
+            .map(|roll| Self::value(roll.tens[roll.kept], roll.units))
             .collect();
 
-        let mut text = values
+        let mut text = rolls
             .iter()
             .map(Self::format_dice)
             .collect::<Vec<String>>()
             .join(", ");
-        text = format!("[{}] {}df", text, self.count);
+        let notation = format!(
+            "{}d%{}",
+            self.count,
+            match (self.bonus, self.penalty) {
+                (0, 0) => String::new(),
+                (bonus, 0) => format!("b{}", bonus),
+                (0, penalty) => format!("p{}", penalty),
+                (bonus, penalty) => format!("b{}p{}", bonus, penalty),
+            }
+        );
+        text = format!("[{}] {}", text, notation);
+
+        roller.record(
+            &notation,
+            10,
+            rolls.iter().flat_map(|roll| {
+                let tens = roll.tens.iter().enumerate();
+                let tens = tens.map(|(i, tens)| (*tens, RollKind::Roll, i == roll.kept));
+                tens.chain([(roll.units, RollKind::Roll, true)])
+            }),
+        );
 
         Ok(RogCons {
             value: values.iter().sum(),
@@ -238,18 +1008,59 @@ impl FateDice {
             values,
             boolean: false,
             dice: self.count as u32,
+            glitch: false,
+            botch: false,
+            tier: None,
+            margin: None,
+            degrees: None,
+            crit_successes: 0,
+            crit_failures: 0,
         })
     }
 }
 
-#[derive(Debug)]
-pub enum RogErr {
-    CountMax,
-    CountMin,
-    ExplodeMin,
-    ExplodeChanceHigh,
-    InvalidAttribute,
-    AttributeMax,
-    LineMax,
-    UnknownError,
+/// Grade of a `d% <= SKILL` check, following Call of Cthulhu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuccessTier {
+    /// 100, or 96 and up when the skill is below 50.
+    Fumble,
+    Failure,
+    Regular,
+    /// At most half the skill.
+    Hard,
+    /// At most a fifth of the skill.
+    Extreme,
+    /// A roll of 1.
+    Critical,
+}
+impl SuccessTier {
+    pub fn grade(roll: f64, skill: f64) -> Self {
+        // A 100 fumbles even when the skill reaches it.
+        if roll >= 100. {
+            SuccessTier::Fumble
+        } else if roll > skill {
+            match skill < 50. && roll >= 96. {
+                true => SuccessTier::Fumble,
+                false => SuccessTier::Failure,
+            }
+        } else if roll <= 1. {
+            SuccessTier::Critical
+        } else if roll <= (skill / 5.).floor() {
+            SuccessTier::Extreme
+        } else if roll <= (skill / 2.).floor() {
+            SuccessTier::Hard
+        } else {
+            SuccessTier::Regular
+        }
+    }
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SuccessTier::Fumble => "fumble",
+            SuccessTier::Failure => "failure",
+            SuccessTier::Regular => "regular",
+            SuccessTier::Hard => "hard",
+            SuccessTier::Extreme => "extreme",
+            SuccessTier::Critical => "critical",
+        }
+    }
 }
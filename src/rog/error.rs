@@ -0,0 +1,249 @@
+use super::dice::{Compare, CompareOp};
+use super::locale::Locale;
+
+pub const MAX_DICE: usize = 100;
+pub const MAX_ATTRIBUTES: usize = 100;
+pub const MAX_LINES: usize = 100;
+pub const MAX_REROLLS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RogErr {
+    /// Requested dice count.
+    CountMax(usize),
+    /// Requested number of sides.
+    CountMin(usize),
+    /// Requested explosion value.
+    ExplodeMin(usize),
+    /// Requested explosion point and number of sides of the die.
+    ExplodeChanceHigh(Compare<f64>, usize),
+    /// Maximum number of explosions of a die, which was reached.
+    ExplodeDepth(usize),
+    /// Number of sides of a die whose every face would be rerolled.
+    RerollAll(usize),
+    RerollMax,
+    /// An explosion or success option written twice for the same dice.
+    OptionRepeated,
+    /// A success count on a single die, which used to be a test.
+    PoolSingle,
+    /// The dice of an `aro` roll kept coming out the same.
+    AroMax,
+    /// How many different faces or cards there were to draw.
+    DrawMax(usize),
+    /// Bounds of a `d[A..B]` die with more faces than can be counted.
+    RangeMax(f64, f64),
+    /// Name of the missing attribute.
+    InvalidAttribute(String),
+    AttributeMax,
+    LineMax,
+    /// The seed as written by the user.
+    InvalidSeed(String),
+    /// A secret was given without the nonce it is combined with.
+    NonceMissing,
+    /// A seed and a secret were given together.
+    SeedAndSecret,
+    Syntax,
+    UnknownError,
+}
+impl RogErr {
+    /// Stable identifier of the error, safe to match on from the JS side.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RogErr::CountMax(_) => "COUNT_MAX",
+            RogErr::CountMin(_) => "COUNT_MIN",
+            RogErr::ExplodeMin(_) => "EXPLODE_MIN",
+            RogErr::ExplodeChanceHigh(..) => "EXPLODE_CHANCE_HIGH",
+            RogErr::ExplodeDepth(_) => "EXPLODE_DEPTH",
+            RogErr::RerollAll(_) => "REROLL_ALL",
+            RogErr::RerollMax => "REROLL_MAX",
+            RogErr::OptionRepeated => "OPTION_REPEATED",
+            RogErr::PoolSingle => "POOL_SINGLE",
+            RogErr::AroMax => "ARO_MAX",
+            RogErr::DrawMax(_) => "DRAW_MAX",
+            RogErr::RangeMax(..) => "RANGE_MAX",
+            RogErr::InvalidAttribute(_) => "INVALID_ATTRIBUTE",
+            RogErr::AttributeMax => "ATTRIBUTE_MAX",
+            RogErr::LineMax => "LINE_MAX",
+            RogErr::InvalidSeed(_) => "INVALID_SEED",
+            RogErr::NonceMissing => "NONCE_MISSING",
+            RogErr::SeedAndSecret => "SEED_AND_SECRET",
+            RogErr::Syntax => "SYNTAX",
+            RogErr::UnknownError => "UNKNOWN",
+        }
+    }
+    pub fn message(&self, locale: Locale) -> String {
+        match locale {
+            Locale::PtBr => match self {
+                RogErr::CountMax(count) => {
+                    format!("{} dados é o máximo, você pediu {}", MAX_DICE, count)
+                }
+                RogErr::CountMin(sides) => {
+                    format!(
+                        "um dado precisa de pelo menos 2 lados, você pediu {}",
+                        sides
+                    )
+                }
+                RogErr::ExplodeMin(value) => {
+                    format!(
+                        "a explosão precisa ser de pelo menos 2, você pediu {}",
+                        value
+                    )
+                }
+                RogErr::ExplodeChanceHigh(compare, sides) => format!(
+                    "a chance de explodir não pode passar de 99.99%, mas um d{} explode {}",
+                    sides,
+                    describe_compare(compare, locale)
+                ),
+                RogErr::ExplodeDepth(depth) => {
+                    format!("a explosão chegou ao limite de {} vezes", depth)
+                }
+                RogErr::RerollAll(sides) => {
+                    format!("todos os lados de um d{} seriam rolados de novo", sides)
+                }
+                RogErr::RerollMax => {
+                    format!("um dado foi rolado de novo {} vezes, o máximo", MAX_REROLLS)
+                }
+                RogErr::OptionRepeated => {
+                    String::from("um dado só pode ter uma explosão e uma contagem de sucessos")
+                }
+                RogErr::PoolSingle => String::from(
+                    "contar sucessos precisa de mais de um dado, para testar o resultado use espaços: `d20 >= 10`",
+                ),
+                RogErr::AroMax => {
+                    format!(
+                        "os dados saíram iguais até somarem {} dados, o máximo",
+                        MAX_DICE
+                    )
+                }
+                RogErr::DrawMax(count) => {
+                    format!("só existem {} resultados diferentes para tirar", count)
+                }
+                RogErr::RangeMax(low, high) => {
+                    format!("o intervalo [{}..{}] tem faces demais", low, high)
+                }
+                RogErr::InvalidAttribute(name) => format!("atributo {} não existe", name),
+                RogErr::AttributeMax => format!("{} atributos é o máximo", MAX_ATTRIBUTES),
+                RogErr::LineMax => format!("{} linhas é o máximo", MAX_LINES),
+                RogErr::InvalidSeed(seed) => {
+                    format!("semente {} inválida, use até 64 dígitos hexadecimais", seed)
+                }
+                RogErr::NonceMissing => String::from("rolagens com segredo precisam de um nonce"),
+                RogErr::SeedAndSecret => String::from("use uma semente ou um segredo, não os dois"),
+                RogErr::Syntax => String::from("expressão inválida"),
+                RogErr::UnknownError => String::from("erro desconhecido"),
+            },
+            Locale::En => match self {
+                RogErr::CountMax(count) => {
+                    format!("{} dice is the maximum, you asked for {}", MAX_DICE, count)
+                }
+                RogErr::CountMin(sides) => {
+                    format!("a die needs at least 2 sides, you asked for {}", sides)
+                }
+                RogErr::ExplodeMin(value) => {
+                    format!("explosions must be on 2 or higher, you asked for {}", value)
+                }
+                RogErr::ExplodeChanceHigh(compare, sides) => format!(
+                    "the explosion chance can't exceed 99.99%, but a d{} explodes {}",
+                    sides,
+                    describe_compare(compare, locale)
+                ),
+                RogErr::ExplodeDepth(depth) => {
+                    format!("the explosion reached its limit of {} times", depth)
+                }
+                RogErr::RerollAll(sides) => {
+                    format!("every side of a d{} would be rerolled", sides)
+                }
+                RogErr::RerollMax => {
+                    format!("a die was rerolled {} times, the maximum", MAX_REROLLS)
+                }
+                RogErr::OptionRepeated => {
+                    String::from("a die can only have one explosion and one success count")
+                }
+                RogErr::PoolSingle => String::from(
+                    "counting successes needs more than one die, to test the result use spaces: `d20 >= 10`",
+                ),
+                RogErr::AroMax => {
+                    format!(
+                        "the dice came out the same up to {} dice, the maximum",
+                        MAX_DICE
+                    )
+                }
+                RogErr::DrawMax(count) => {
+                    format!("there are only {} different results to draw", count)
+                }
+                RogErr::RangeMax(low, high) => {
+                    format!("the range [{}..{}] has too many faces", low, high)
+                }
+                RogErr::InvalidAttribute(name) => format!("attribute {} does not exist", name),
+                RogErr::AttributeMax => format!("{} attributes is the maximum", MAX_ATTRIBUTES),
+                RogErr::LineMax => format!("{} lines is the maximum", MAX_LINES),
+                RogErr::InvalidSeed(seed) => {
+                    format!("invalid seed {}, use up to 64 hexadecimal digits", seed)
+                }
+                RogErr::NonceMissing => String::from("rolls with a secret need a nonce"),
+                RogErr::SeedAndSecret => String::from("use either a seed or a secret, not both"),
+                RogErr::Syntax => String::from("invalid expression"),
+                RogErr::UnknownError => String::from("unknown error"),
+            },
+        }
+    }
+}
+/// Which faces a compare point matches, as in "explodes on 5 or higher".
+fn describe_compare(compare: &Compare<f64>, locale: Locale) -> String {
+    let value = compare.value;
+    match (locale, compare.op) {
+        (Locale::PtBr, CompareOp::Eq) => format!("em {}", value),
+        (Locale::PtBr, CompareOp::Less) => format!("em menos de {}", value),
+        (Locale::PtBr, CompareOp::LessEq) => format!("em {} ou menos", value),
+        (Locale::PtBr, CompareOp::Greater) => format!("em mais de {}", value),
+        (Locale::PtBr, CompareOp::GreaterEq) => format!("em {} ou mais", value),
+        (Locale::En, CompareOp::Eq) => format!("on {}", value),
+        (Locale::En, CompareOp::Less) => format!("below {}", value),
+        (Locale::En, CompareOp::LessEq) => format!("on {} or lower", value),
+        (Locale::En, CompareOp::Greater) => format!("above {}", value),
+        (Locale::En, CompareOp::GreaterEq) => format!("on {} or higher", value),
+    }
+}
+
+impl std::fmt::Display for RogErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Locale::default()))
+    }
+}
+
+/// A `RogErr` together with where it happened in the input.
+///
+/// `line` is 1-based and `span` holds byte offsets. While the error travels
+/// through the evaluator they are relative to the current line, `RogError::locate`
+/// turns them into offsets of the whole input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RogError {
+    pub kind: RogErr,
+    pub line: usize,
+    pub span: Option<(usize, usize)>,
+    pub expected: Vec<String>,
+}
+impl RogError {
+    pub fn new(kind: RogErr) -> Self {
+        Self {
+            kind,
+            line: 0,
+            span: None,
+            expected: vec![],
+        }
+    }
+    /// Sets the span, unless a more specific one was already set.
+    pub fn at(mut self, start: usize, end: usize) -> Self {
+        self.span.get_or_insert((start, end));
+        self
+    }
+    pub fn locate(mut self, line: usize, offset: usize) -> Self {
+        self.line = line;
+        self.span = self.span.map(|(start, end)| (start + offset, end + offset));
+        self
+    }
+}
+impl From<RogErr> for RogError {
+    fn from(kind: RogErr) -> Self {
+        Self::new(kind)
+    }
+}
@@ -0,0 +1,20 @@
+/// Language used when rendering messages for the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    PtBr,
+    En,
+}
+impl Locale {
+    /// Reads a language tag such as `pt-BR` or `en-US`, ignoring the region
+    /// when it is not needed to tell the locales apart.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        let language = tag.split('-').next().unwrap_or_default();
+        match language {
+            "pt" => Some(Locale::PtBr),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+}
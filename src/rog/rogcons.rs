@@ -1,15 +1,39 @@
 use std::vec;
 
+use super::dice::SuccessTier;
+
+/// Points of margin that make up a degree of success or failure, as in Warhammer.
+pub const DEGREE_SIZE: f64 = 10.;
+
+#[cfg(feature = "napi")]
 use napi_derive::napi;
 
-#[derive(Debug)]
-#[napi(object)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "napi", napi(object))]
 pub struct RogCons {
     pub value: f64,
     pub values: Vec<f64>,
     pub text: String,
     pub boolean: bool,
     pub dice: u32,
+    /// More than half the dice of a success pool were failures.
+    pub glitch: bool,
+    /// A success pool had failures and no successes.
+    pub botch: bool,
+    /// Grade of a `d% <= SKILL` check: `fumble`, `failure`, `regular`, `hard`,
+    /// `extreme` or `critical`, see `SuccessTier`.
+    pub tier: Option<String>,
+    /// How far a `<`, `<=`, `>` or `>=` test passed by, negative when it failed.
+    /// For roll-under tests (`d100 <= 60`) that's the target minus the roll.
+    pub margin: Option<f64>,
+    /// Whole `DEGREE_SIZE`s in the margin, of success or of failure.
+    pub degrees: Option<u32>,
+    /// Kept dice that were critical successes, on their highest face or
+    /// matching `cs`.
+    pub crit_successes: u32,
+    /// Kept dice that were critical failures, on their lowest face or
+    /// matching `cf`.
+    pub crit_failures: u32,
 }
 
 impl RogCons {
@@ -24,6 +48,29 @@ impl RogCons {
             },
             boolean: false,
             dice: 0,
+            glitch: false,
+            botch: false,
+            tier: None,
+            margin: None,
+            degrees: None,
+            crit_successes: 0,
+            crit_failures: 0,
+        }
+    }
+    /// Keeps how far a test passed by along with its degrees.
+    fn with_margin(mut self, margin: f64) -> Self {
+        self.margin = Some(margin);
+        self.degrees = Some((margin.abs() / DEGREE_SIZE).floor() as u32);
+        self
+    }
+    /// Carries the dice count, crits and pool flags of `operands` over to `self`.
+    fn inherit(&mut self, operands: &[&RogCons]) {
+        for operand in operands {
+            self.dice += operand.dice;
+            self.crit_successes += operand.crit_successes;
+            self.crit_failures += operand.crit_failures;
+            self.glitch |= operand.glitch;
+            self.botch |= operand.botch;
         }
     }
     pub fn hyper_add(mut self, rhs: Self) -> Self {
@@ -37,7 +84,7 @@ impl RogCons {
             .join(", ");
         self.text = format!("[{}] ⟵ {} ++ {}", joined, self.text, rhs.text);
         self.boolean = false;
-        self.dice += rhs.dice;
+        self.inherit(&[&rhs]);
         self
     }
     pub fn hyper_sub(mut self, rhs: Self) -> Self {
@@ -51,42 +98,57 @@ impl RogCons {
             .join(", ");
         self.text = format!("[{}] ⟵ {} -- {}", joined, self.text, rhs.text);
         self.boolean = false;
-        self.dice += rhs.dice;
+        self.inherit(&[&rhs]);
         self
     }
     pub fn less(self, rhs: Self) -> Self {
         let value = if self.value < rhs.value { 1. } else { 0. };
+        let margin = rhs.value - self.value;
         let mut cons = Self::from_number(value, format!("{} < {}", self.text, rhs.text));
         cons.boolean = true;
-        cons.dice = self.dice + rhs.dice;
-        cons
+        cons.inherit(&[&self, &rhs]);
+        cons.with_margin(margin)
     }
     pub fn less_eq(self, rhs: Self) -> Self {
         let value = if self.value <= rhs.value { 1. } else { 0. };
+        let margin = rhs.value - self.value;
         let mut cons = Self::from_number(value, format!("{} <= {}", self.text, rhs.text));
         cons.boolean = true;
-        cons.dice = self.dice + rhs.dice;
-        cons
+        cons.inherit(&[&self, &rhs]);
+        cons.with_margin(margin)
     }
     pub fn greater(self, rhs: Self) -> Self {
         let value = if self.value > rhs.value { 1. } else { 0. };
+        let margin = self.value - rhs.value;
         let mut cons = Self::from_number(value, format!("{} > {}", self.text, rhs.text));
         cons.boolean = true;
-        cons.dice = self.dice + rhs.dice;
-        cons
+        cons.inherit(&[&self, &rhs]);
+        cons.with_margin(margin)
     }
     pub fn greater_eq(self, rhs: Self) -> Self {
         let value = if self.value >= rhs.value { 1. } else { 0. };
+        let margin = self.value - rhs.value;
         let mut cons = Self::from_number(value, format!("{} >= {}", self.text, rhs.text));
         cons.boolean = true;
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
+        cons.with_margin(margin)
+    }
+    /// `d% <= SKILL`, graded as a skill check.
+    pub fn skill_check(self, rhs: Self) -> Self {
+        let tier = SuccessTier::grade(self.value, rhs.value);
+        let mut cons = self.less_eq(rhs);
+        if tier == SuccessTier::Fumble {
+            cons.value = 0.;
+            cons.values = vec![0.];
+        }
+        cons.tier = Some(tier.as_str().to_string());
         cons
     }
     pub fn eq(self, rhs: Self) -> Self {
         let value = if self.value == rhs.value { 1. } else { 0. };
         let mut cons = Self::from_number(value, format!("{} = {}", self.text, rhs.text));
         cons.boolean = true;
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
     pub fn percent(mut self) -> Self {
@@ -115,10 +177,11 @@ impl RogCons {
     }
 }
 
-impl ToString for RogCons {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for RogCons {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.boolean {
-            format!(
+            write!(
+                f,
                 "` {} ` ⟵ {}",
                 if self.value == 0. {
                     "Falha!"
@@ -128,7 +191,7 @@ impl ToString for RogCons {
                 self.text
             )
         } else {
-            format!("` {} ` ⟵ {}", self.value, self.text)
+            write!(f, "` {} ` ⟵ {}", self.value, self.text)
         }
     }
 }
@@ -140,7 +203,7 @@ impl std::ops::Add for RogCons {
             self.value + rhs.value,
             format!("{} + {}", self.text, rhs.text),
         );
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -151,7 +214,7 @@ impl std::ops::Sub for RogCons {
             self.value - rhs.value,
             format!("{} - {}", self.text, rhs.text),
         );
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -162,7 +225,7 @@ impl std::ops::Mul for RogCons {
             self.value * rhs.value,
             format!("{} \\* {}", self.text, rhs.text),
         );
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -173,7 +236,7 @@ impl std::ops::Div for RogCons {
             self.value / rhs.value,
             format!("{} / {}", self.text, rhs.text),
         );
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -187,7 +250,7 @@ impl std::ops::BitAnd for RogCons {
             format!("{} & {}", self.text, rhs.text),
         );
         cons.boolean = true;
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -201,7 +264,7 @@ impl std::ops::BitOr for RogCons {
             format!("{} | {}", self.text, rhs.text),
         );
         cons.boolean = true;
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -210,9 +273,9 @@ impl std::ops::BitOr for RogCons {
 impl std::ops::Shl for RogCons {
     type Output = RogCons;
     fn shl(self, rhs: Self) -> Self::Output {
-        let value = self.values.into_iter().filter(|v| v <= &rhs.value).count();
+        let value = self.values.iter().filter(|v| **v <= rhs.value).count();
         let mut cons = RogCons::from_number(value as f64, format!("{} << {}", self.text, rhs.text));
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -220,9 +283,9 @@ impl std::ops::Shl for RogCons {
 impl std::ops::Shr for RogCons {
     type Output = RogCons;
     fn shr(self, rhs: Self) -> Self::Output {
-        let value = self.values.into_iter().filter(|v| v >= &rhs.value).count();
+        let value = self.values.iter().filter(|v| **v >= rhs.value).count();
         let mut cons = RogCons::from_number(value as f64, format!("{} >> {}", self.text, rhs.text));
-        cons.dice = self.dice + rhs.dice;
+        cons.inherit(&[&self, &rhs]);
         cons
     }
 }
@@ -230,7 +293,7 @@ impl std::ops::Neg for RogCons {
     type Output = RogCons;
     fn neg(self) -> Self::Output {
         let mut cons = RogCons::from_number(-self.value, format!("-{}", self.text));
-        cons.dice = self.dice;
+        cons.inherit(&[&self]);
         cons
     }
 }
@@ -239,7 +302,7 @@ impl std::ops::Not for RogCons {
     fn not(self) -> Self::Output {
         let value = if self.value != 0. { 0. } else { 1. };
         let mut cons = Self::from_number(value, format!("!{}", self.text));
-        cons.dice = self.dice;
+        cons.inherit(&[&self]);
         cons.boolean = true;
         cons
     }
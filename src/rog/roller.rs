@@ -0,0 +1,259 @@
+use super::error::*;
+#[cfg(feature = "napi")]
+use napi_derive::napi;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Cards left in each deck, by deck name, as indexes into the whole deck in
+/// the order they will be drawn.
+pub type Decks = HashMap<String, Vec<u32>>;
+
+/// Seed of a `Roller`. Rolling the same script with the same seed always
+/// gives the same results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seed(pub [u8; 32]);
+impl Seed {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+    pub fn from_u64(seed: u64) -> Self {
+        let mut bytes = [0; 32];
+        bytes[24..].copy_from_slice(&seed.to_be_bytes());
+        Self(bytes)
+    }
+    /// Reads a seed written in hexadecimal, with up to 64 digits.
+    /// Shorter seeds are padded with zeros on the left.
+    pub fn from_hex(hex: &str) -> Result<Self, RogErr> {
+        let hex = hex.trim();
+        if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(RogErr::InvalidSeed(hex.to_string()));
+        }
+        let padded = format!("{:0>64}", hex);
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&padded[i * 2..i * 2 + 2], 16)
+                .map_err(|_| RogErr::InvalidSeed(hex.to_string()))?;
+        }
+        Ok(Self(bytes))
+    }
+    /// Derives the seed of a verifiable roll from a secret only the GM
+    /// knows and a public nonce, chosen after the GM published the
+    /// `commitment` of the secret.
+    pub fn from_secret(secret: &str, nonce: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update((secret.len() as u64).to_be_bytes());
+        hasher.update(secret);
+        hasher.update(nonce);
+        Self(hasher.finalize().into())
+    }
+    /// Seed of a roll from the options of a caller: a `seed` in hexadecimal,
+    /// a `secret` along with its `nonce`, or a random one when neither is given.
+    /// Without a nonce the GM would pick the whole seed, so it is required.
+    pub fn from_options(
+        seed: Option<&str>,
+        secret: Option<&str>,
+        nonce: Option<&str>,
+    ) -> Result<Self, RogErr> {
+        let nonce = nonce.filter(|nonce| !nonce.trim().is_empty());
+        match (seed, secret, nonce) {
+            (Some(_), Some(_), _) => Err(RogErr::SeedAndSecret),
+            (None, Some(_), None) => Err(RogErr::NonceMissing),
+            (None, Some(secret), Some(nonce)) => Ok(Self::from_secret(secret, nonce)),
+            (Some(seed), None, _) => Self::from_hex(seed),
+            (None, None, _) => Ok(Self::random()),
+        }
+    }
+    pub fn to_hex(self) -> String {
+        Self::hex(&self.0)
+    }
+    /// SHA-256 hash of `secret`, in hexadecimal. The GM publishes it before
+    /// any nonce is known, binding themselves to the secret without giving
+    /// it away.
+    pub fn commitment(secret: &str) -> String {
+        Self::hex(&Sha256::digest(secret))
+    }
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollKind {
+    /// One of the dice asked for in the notation.
+    Roll,
+    /// Extra die rolled because the previous one exploded.
+    Explosion,
+    /// Extra die rolled because all dice of an `aro` roll came out the same.
+    Aro,
+    /// Die rolled again to replace one that matched a reroll option.
+    Reroll,
+}
+impl RollKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RollKind::Roll => "roll",
+            RollKind::Explosion => "explosion",
+            RollKind::Aro => "aro",
+            RollKind::Reroll => "reroll",
+        }
+    }
+}
+
+/// A single random draw, as kept in the audit log of a `Roller`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+pub struct RollRecord {
+    /// 1-based line of the script the draw was made in.
+    pub line: u32,
+    /// Index of the dice term in the script, counting every term rolled so far.
+    pub term: u32,
+    /// Notation of the dice term, such as `4d6!k3`.
+    pub notation: String,
+    /// Number of sides of the die. Fate dice have 3 sides, valued -1, 0 and 1.
+    pub sides: u32,
+    pub value: f64,
+    /// `roll`, `explosion`, `aro` or `reroll`, see `RollKind`.
+    pub kind: String,
+    /// Whether the value counted towards the result or was dropped.
+    pub kept: bool,
+}
+
+/// Source of every random draw made while evaluating a script.
+pub struct Roller {
+    seed: Seed,
+    rng: ChaCha20Rng,
+    line: usize,
+    terms: usize,
+    log: Vec<RollRecord>,
+    decks: Decks,
+}
+impl Roller {
+    pub fn new(seed: Seed) -> Self {
+        Self {
+            seed,
+            rng: ChaCha20Rng::from_seed(seed.0),
+            line: 0,
+            terms: 0,
+            log: vec![],
+            decks: Decks::new(),
+        }
+    }
+    /// Continues drawing from `decks`, as left by an earlier roller.
+    pub fn with_decks(mut self, decks: Decks) -> Self {
+        self.decks = decks;
+        self
+    }
+    pub fn decks(&self) -> &Decks {
+        &self.decks
+    }
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+    pub fn set_line(&mut self, line: usize) {
+        self.line = line;
+    }
+    /// Every draw made so far, in the order they were made.
+    pub fn into_log(self) -> Vec<RollRecord> {
+        self.log
+    }
+    /// Adds the draws of one dice term to the audit log, in the order they were made.
+    pub fn record(
+        &mut self,
+        notation: &str,
+        sides: usize,
+        rolls: impl Iterator<Item = (f64, RollKind, bool)>,
+    ) {
+        for (value, kind, kept) in rolls {
+            self.log.push(RollRecord {
+                line: self.line as u32,
+                term: self.terms as u32,
+                notation: notation.to_string(),
+                sides: sides as u32,
+                value,
+                kind: kind.as_str().to_string(),
+                kept,
+            });
+        }
+        self.terms += 1;
+    }
+    /// Rolls a die with faces `1..=sides`, each face equally likely.
+    pub fn die(&mut self, sides: usize) -> usize {
+        self.rng.gen_range(1..=sides)
+    }
+    /// Draws the top card of the deck `name`, which has `size` cards in
+    /// total, giving its index. An empty deck is shuffled again first, with
+    /// every card but those of `hand`, the cards already drawn by the same term.
+    pub fn draw(&mut self, name: &str, size: usize, hand: &[usize]) -> usize {
+        let mut deck = self.decks.remove(name).unwrap_or_default();
+        // Cards that no longer exist, if the deck got smaller.
+        deck.retain(|card| (*card as usize) < size);
+        if deck.is_empty() {
+            deck = (0..size as u32)
+                .filter(|card| !hand.contains(&(*card as usize)))
+                .collect();
+            for i in (1..deck.len()).rev() {
+                deck.swap(i, self.rng.gen_range(0..=i));
+            }
+        }
+        let card = deck.remove(0);
+        self.decks.insert(name.to_string(), deck);
+        card as usize
+    }
+    /// Rolls a fate die, giving -1, 0 or 1.
+    pub fn fate(&mut self) -> i32 {
+        self.rng.gen_range(-1..=1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Critical value of the chi-square distribution with `df` degrees of
+    /// freedom at p = 0.001, by the Wilson-Hilferty approximation.
+    fn chi_square_critical(df: usize) -> f64 {
+        let df = df as f64;
+        let z = 3.09;
+        df * (1. - 2. / (9. * df) + z * (2. / (9. * df)).sqrt()).powi(3)
+    }
+
+    fn chi_square(counts: &[usize], expected: f64) -> f64 {
+        counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    #[test]
+    fn dice_are_uniform() {
+        for (seed, sides) in [2, 3, 6, 7, 10, 12, 20, 100, 1000].into_iter().enumerate() {
+            let mut roller = Roller::new(Seed::from_u64(seed as u64));
+            let rolls = sides * 1000;
+            let mut counts = vec![0; sides];
+            for _ in 0..rolls {
+                let value = roller.die(sides);
+                assert!((1..=sides).contains(&value));
+                counts[value - 1] += 1;
+            }
+            let statistic = chi_square(&counts, (rolls / sides) as f64);
+            assert!(
+                statistic < chi_square_critical(sides - 1),
+                "d{} failed the chi-square test with {}",
+                sides,
+                statistic
+            );
+        }
+    }
+
+    #[test]
+    fn fate_dice_are_uniform() {
+        let mut roller = Roller::new(Seed::from_u64(0));
+        let mut counts = vec![0; 3];
+        for _ in 0..30000 {
+            counts[(roller.fate() + 1) as usize] += 1;
+        }
+        assert!(chi_square(&counts, 10000.) < chi_square_critical(2));
+    }
+}
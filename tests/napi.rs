@@ -0,0 +1,8 @@
+// Test binaries can't link the napi bindings, which only resolve once Node
+// loads the addon, so `run.rs` compiles to nothing under default features.
+// This stands in for it, so `cargo test` says where the suite went.
+#![cfg(feature = "napi")]
+
+#[test]
+#[ignore = "the suite needs the napi bindings off, run `cargo test --no-default-features`"]
+fn integration_tests_run_without_napi() {}
@@ -0,0 +1,458 @@
+// napi symbols are only resolved once Node loads the addon, so test binaries
+// using the crate link against it with `--no-default-features`.
+#![cfg(not(feature = "napi"))]
+
+use froxcript::rog::*;
+use froxcript::*;
+use std::collections::HashMap;
+
+#[test]
+fn runs_without_napi() {
+    let attr_map = HashMap::from([("FOR".to_string(), 3.)]);
+    let output = run("ATQ = 2d6 + FOR", attr_map.clone(), Seed::from_u64(7));
+    assert!(matches!(
+        output.map_err(|e| e.kind),
+        Err(RogErr::InvalidAttribute(attr)) if attr == "ATQ"
+    ));
+
+    let seed = Seed::from_u64(7);
+    let output = run("$ATQ = 2d6 + FOR", attr_map.clone(), seed).unwrap();
+    assert_eq!(output.cons.len(), 1);
+    assert_eq!(output.rolls.len(), 2);
+    assert_eq!(output.attr_map["$ATQ"], output.cons[0].value);
+    assert_eq!(output.commitment, None);
+}
+
+#[test]
+fn secret_rolls_verify_against_their_commitment() {
+    let input = "3d6 + FOR";
+    let attr_map = HashMap::from([("FOR".to_string(), 3.)]);
+    let commitment = Seed::commitment("segredo do mestre");
+    let seed = Seed::from_secret("segredo do mestre", "sessão 12");
+    let output = run(input, attr_map.clone(), seed).unwrap();
+    let verify = |secret, nonce| {
+        let (attr_map, decks) = (attr_map.clone(), Decks::new());
+        verify_run(
+            input,
+            attr_map,
+            decks,
+            secret,
+            nonce,
+            &commitment,
+            &output.cons,
+        )
+    };
+    assert!(verify("segredo do mestre", "sessão 12"));
+    assert!(!verify("outro segredo", "sessão 12"));
+    assert!(!verify("segredo do mestre", "sessão 13"));
+
+    let seed = |seed, secret, nonce| Seed::from_options(seed, secret, nonce).map_err(|e| e.code());
+    let secret = Some("segredo do mestre");
+    assert_eq!(
+        seed(None, secret, Some("sessão 12")),
+        Ok(Seed::from_secret("segredo do mestre", "sessão 12"))
+    );
+    assert_eq!(seed(None, secret, None), Err("NONCE_MISSING"));
+    assert_eq!(seed(None, secret, Some(" ")), Err("NONCE_MISSING"));
+    assert_eq!(
+        seed(Some("7"), secret, Some("sessão 12")),
+        Err("SEED_AND_SECRET")
+    );
+}
+
+#[test]
+fn scripts_list_what_they_read() {
+    let script = compile("$X = 2dMED + d{MED,FOR}\nATQ += d$X + d[1..DES]").unwrap();
+    assert_eq!(script.attributes(), ["ATQ", "DES"]);
+    assert_eq!(script.face_sets(), ["FOR", "MED"]);
+    assert_eq!(script.variables(), ["$X"]);
+
+    let script = compile("cINICIATIVA + 2cTAROT + c").unwrap();
+    assert_eq!(script.attributes(), Vec::<String>::new());
+    assert_eq!(script.face_sets(), ["INICIATIVA"]);
+}
+
+#[test]
+fn success_pools_count_successes_minus_failures() {
+    for seed in 0..50 {
+        let output = run("4d10>=8f<3", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let cons = &output.cons[0];
+        let successes = cons.values.iter().filter(|v| **v >= 8.).count();
+        let failures = cons.values.iter().filter(|v| **v < 3.).count();
+        assert_eq!(cons.value, successes as f64 - failures as f64);
+        assert_eq!(cons.glitch, failures > 2);
+        assert_eq!(cons.botch, successes == 0 && failures > 0);
+    }
+}
+
+#[test]
+fn bare_compares_on_one_die_are_refused() {
+    for input in ["d20>=10", "1d100<=50", "dF>=1"] {
+        let output = run(input, HashMap::new(), Seed::from_u64(0));
+        assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::PoolSingle));
+    }
+    let attr_map = HashMap::from([("DES".to_string(), 1.)]);
+    for input in ["d20 >= 10", "d10>=8f1", "DESd10>=8"] {
+        assert!(run(input, attr_map.clone(), Seed::from_u64(0)).is_ok());
+    }
+}
+
+#[test]
+fn custom_faces_roll_their_own_values() {
+    let attr_map = HashMap::from([
+        ("MED.1".to_string(), 2.),
+        ("MED.2".to_string(), 3.),
+        ("MED.3".to_string(), 5.),
+    ]);
+    for seed in 0..20 {
+        let seed = Seed::from_u64(seed);
+        let output = run("3d[-3..3]\n4d{0,0.5,1}\n2dMED", attr_map.clone(), seed).unwrap();
+        assert!(output.cons[0]
+            .values
+            .iter()
+            .all(|v| (-3. ..=3.).contains(v)));
+        assert!(output.cons[1]
+            .values
+            .iter()
+            .all(|v| [0., 0.5, 1.].contains(v)));
+        assert!(output.cons[2]
+            .values
+            .iter()
+            .all(|v| [2., 3., 5.].contains(v)));
+    }
+}
+
+#[test]
+fn skill_checks_are_graded() {
+    let grades = [
+        (1., 40., SuccessTier::Critical),
+        (8., 40., SuccessTier::Extreme),
+        (20., 40., SuccessTier::Hard),
+        (40., 40., SuccessTier::Regular),
+        (41., 40., SuccessTier::Failure),
+        (96., 40., SuccessTier::Fumble),
+        (96., 60., SuccessTier::Failure),
+        (100., 60., SuccessTier::Fumble),
+        (100., 100., SuccessTier::Fumble),
+        (99., 100., SuccessTier::Regular),
+    ];
+    for (roll, skill, tier) in grades {
+        assert_eq!(SuccessTier::grade(roll, skill), tier);
+    }
+    let output = run("d% <= 100", HashMap::new(), Seed::from_u64(0x48)).unwrap();
+    assert!(output.cons[0].text.ends_with("[**100**(00+0)] 1d% <= 100"));
+    assert_eq!(output.cons[0].tier.as_deref(), Some("fumble"));
+    assert_eq!(output.cons[0].value, 0.);
+
+    for seed in 0..20 {
+        let output = run("d%b2 <= 50", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let cons = &output.cons[0];
+        let failed = matches!(cons.tier.as_deref(), Some("failure" | "fumble"));
+        assert!(cons.boolean);
+        assert_eq!(cons.value == 0., failed);
+    }
+}
+
+#[test]
+fn tests_keep_their_margin() {
+    let output = run(
+        "d100 <= 52\n$MARGEM + $GRAUS",
+        HashMap::new(),
+        Seed::from_u64(1),
+    )
+    .unwrap();
+    let roll = output.rolls[0].value;
+    let test = &output.cons[0];
+    assert_eq!(test.margin, Some(52. - roll));
+    assert_eq!(
+        test.degrees,
+        Some(((52. - roll).abs() / 10.).floor() as u32)
+    );
+    assert_eq!(
+        output.cons[1].value,
+        52. - roll + test.degrees.unwrap() as f64
+    );
+}
+
+#[test]
+fn margins_count_towards_the_attribute_limit() {
+    let attributes = |count| (0..count).map(|i| (format!("ATR{}", i), 1.)).collect();
+    let output = run("d100 <= 52", attributes(98), Seed::from_u64(1));
+    assert_eq!(output.unwrap().attr_map.len(), MAX_ATTRIBUTES);
+    let output = run("d100 <= 52", attributes(99), Seed::from_u64(1));
+    assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::AttributeMax));
+
+    // Lines that only read the sheet work whatever its size.
+    let output = run("1d20", attributes(150), Seed::from_u64(1));
+    assert_eq!(output.unwrap().attr_map.len(), 150);
+    let output = run("d100 <= 52\nd100 <= 52", attributes(149), Seed::from_u64(1));
+    assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::AttributeMax));
+}
+
+#[test]
+fn decks_carry_over_between_runs() {
+    let mut decks = Decks::new();
+    let mut drawn = vec![];
+    for seed in 0..4 {
+        let script = compile("13c").unwrap();
+        let output = run_script(&script, HashMap::new(), decks, Seed::from_u64(seed)).unwrap();
+        drawn.extend(output.rolls.iter().map(|r| r.value));
+        decks = output.decks;
+    }
+    assert_eq!(decks["BARALHO"], vec![]);
+    drawn.sort_by(f64::total_cmp);
+    let deck: Vec<f64> = (2..=14).flat_map(|rank| [rank as f64; 4]).collect();
+    assert_eq!(drawn, deck);
+}
+
+#[test]
+fn decks_reshuffled_mid_draw_leave_out_the_hand() {
+    let decks = Decks::from([("BARALHO".to_string(), vec![0])]);
+    let script = compile("52c").unwrap();
+    let output = run_script(&script, HashMap::new(), decks, Seed::from_u64(5)).unwrap();
+    assert_eq!(output.decks["BARALHO"], vec![]);
+    let mut drawn: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+    drawn.sort_by(f64::total_cmp);
+    let deck: Vec<f64> = (2..=14).flat_map(|rank| [rank as f64; 4]).collect();
+    assert_eq!(drawn, deck);
+}
+
+#[test]
+fn clamped_dice_count_as_clamped() {
+    for seed in 0..20 {
+        let output = run("8d6min3max5", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let cons = &output.cons[0];
+        let rolled: Vec<f64> = output.rolls.iter().map(|r| r.value.clamp(3., 5.)).collect();
+        assert!(cons.values.iter().all(|v| (3. ..=5.).contains(v)));
+        assert_eq!(cons.value, rolled.iter().sum::<f64>());
+    }
+}
+
+#[test]
+fn modifiers_apply_in_order() {
+    for seed in 0..20 {
+        let output = run("8d6kl5k2", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let mut rolled: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+        rolled.sort_by(f64::total_cmp);
+        assert_eq!(output.cons[0].value, rolled[3] + rolled[4]);
+    }
+    let output = run("8d6r1!d2k3c5", HashMap::new(), Seed::from_u64(3)).unwrap();
+    assert!(output.cons[0].text.ends_with("] 8d6r1!d2k3c5"));
+}
+
+#[test]
+fn modifiers_apply_in_fixed_phases() {
+    // Rerolls and explosions come before clamping, and clamping before keep/drop.
+    for (written, reordered) in [
+        ("4d6k3r1", "4d6r1k3"),
+        ("3d6min3!", "3d6!min3"),
+        ("4d6k2min4", "4d6min4k2"),
+    ] {
+        let written = run(written, HashMap::new(), Seed::from_u64(2)).unwrap();
+        let reordered = run(reordered, HashMap::new(), Seed::from_u64(2)).unwrap();
+        assert_eq!(written.cons[0].values, reordered.cons[0].values);
+        assert_eq!(written.cons[0].value, reordered.cons[0].value);
+    }
+    // Within a phase, the written order counts.
+    let output = run(
+        "2d6min3max2 + 2d6max2min3",
+        HashMap::new(),
+        Seed::from_u64(2),
+    )
+    .unwrap();
+    assert_eq!(output.cons[0].value, 4. + 6.);
+}
+
+#[test]
+fn crits_are_counted_on_kept_dice() {
+    for seed in 0..20 {
+        let output = run("10d20cs>=15cf<=3 + 1", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let rolled: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+        let cons = &output.cons[0];
+        let count = |f: fn(f64) -> bool| rolled.iter().filter(|v| f(**v)).count() as u32;
+        assert_eq!(cons.crit_successes, count(|v| v >= 15.));
+        assert_eq!(cons.crit_failures, count(|v| v <= 3.));
+
+        let output = run("2d20k1cs19", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let cons = &output.cons[0];
+        assert_eq!(cons.crit_successes, (cons.value >= 19.) as u32);
+        assert_eq!(cons.crit_failures, (cons.value == 1.) as u32);
+    }
+
+    // The last crit option written wins, whichever way it is written.
+    for (input, crits) in [("2d20c19cs20", 0), ("2d20cs20c19", 1)] {
+        let output = run(input, HashMap::new(), Seed::from_u64(6)).unwrap();
+        assert!(output.cons[0].values.contains(&19.));
+        assert_eq!(output.cons[0].crit_successes, crits);
+    }
+}
+
+#[test]
+fn fate_dice_take_options_and_read_on_the_ladder() {
+    for seed in 0..20 {
+        let output = run("6dF.1k4", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let mut rolled: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+        rolled.sort_by(|a, b| b.total_cmp(a));
+        let cons = &output.cons[0];
+        assert!(rolled.iter().all(|v| [-1., 0., 1.].contains(v)));
+        assert_eq!(cons.value, rolled[..4].iter().sum::<f64>());
+        let pluses = rolled[..4].iter().filter(|v| **v == 1.).count() as u32;
+        assert_eq!(cons.crit_successes, pluses);
+        let ladder = FateDice::ladder(cons.value).unwrap();
+        assert!(cons
+            .text
+            .starts_with(&format!("` {} {} `", cons.value, ladder)));
+
+        let output = run("4df >> 1", HashMap::new(), Seed::from_u64(seed)).unwrap();
+        let pluses = output.rolls.iter().filter(|r| r.value == 1.).count();
+        assert_eq!(output.cons[0].value, pluses as f64);
+        assert!(output.cons[0].text.starts_with(&format!("` {} `", pluses)));
+    }
+
+    let output = run("4dFr<0", HashMap::new(), Seed::from_u64(2)).unwrap();
+    let text = &output.cons[0].text;
+    assert!(text.ends_with("[~~-~~ 0, ~~-~~ ~~-~~ **+**, **+**, 0] 4dfr<0"));
+}
+
+#[test]
+fn compound_explosions_add_up_into_one_die() {
+    let output = run("1d6!!", HashMap::new(), Seed::from_u64(4)).unwrap();
+    let rolled: Vec<(f64, &str)> = output.rolls.iter().map(|r| (r.value, &*r.kind)).collect();
+    assert_eq!(rolled, [(6., "roll"), (2., "explosion")]);
+    assert_eq!(output.cons[0].values, [8.]);
+    assert!(output.cons[0].text.ends_with("[**8**(6+2)] 1d6!!"));
+}
+
+#[test]
+fn penetrating_explosions_lose_one_each() {
+    let output = run("1d6!p", HashMap::new(), Seed::from_u64(4)).unwrap();
+    let rolled: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+    assert_eq!(rolled, [6., 2.]);
+    assert_eq!(output.cons[0].values, [7.]);
+    assert!(output.cons[0].text.ends_with("[**7**(6+2-1)] 1d6!p"));
+}
+
+#[test]
+fn explosions_follow_their_compare_point_and_cap() {
+    let output = run("1d6!<=2", HashMap::new(), Seed::from_u64(6)).unwrap();
+    let rolled: Vec<(f64, &str)> = output.rolls.iter().map(|r| (r.value, &*r.kind)).collect();
+    assert_eq!(rolled, [(1., "roll"), (4., "explosion")]);
+    assert_eq!(output.cons[0].value, 5.);
+
+    let output = run("1d6!>=2{1}", HashMap::new(), Seed::from_u64(1));
+    assert_eq!(
+        output.map_err(|e| e.kind).err(),
+        Some(RogErr::ExplodeDepth(1))
+    );
+
+    // Sides too many to count saturate, and still explode on the highest.
+    assert!(run("d99999999999999999999!", HashMap::new(), Seed::from_u64(0)).is_ok());
+}
+
+#[test]
+fn rerolls_stop_where_they_should() {
+    let output = run("1d6r<7", HashMap::new(), Seed::from_u64(0));
+    assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::RerollAll(6)));
+
+    let output = run("1d[1..10000]r<10000", HashMap::new(), Seed::from_u64(0));
+    assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::RerollMax));
+
+    // `ro` rerolls once, even when the new value matches again.
+    let output = run("1d[1..10000]ro<10000", HashMap::new(), Seed::from_u64(0)).unwrap();
+    let kinds: Vec<&str> = output.rolls.iter().map(|r| &*r.kind).collect();
+    assert_eq!(kinds, ["roll", "reroll"]);
+    assert!(output.cons[0]
+        .text
+        .ends_with("[~~5635~~ 1592] 1d[1..10000]ro<10000"));
+}
+
+#[test]
+fn percentile_dice_read_double_zero_as_100() {
+    let output = run("d%", HashMap::new(), Seed::from_u64(0x48)).unwrap();
+    let rolled: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+    assert_eq!(rolled, [0., 0.]);
+    assert_eq!(output.cons[0].value, 100.);
+}
+
+#[test]
+fn unique_dice_never_repeat_a_face() {
+    let output = run("5d20u", HashMap::new(), Seed::from_u64(0)).unwrap();
+    let mut rolled: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+    rolled.sort_by(f64::total_cmp);
+    rolled.dedup();
+    assert_eq!(rolled.len(), 5);
+
+    // As many dice as faces roll every face once.
+    let output = run("20d20u", HashMap::new(), Seed::from_u64(0)).unwrap();
+    let mut rolled: Vec<f64> = output.rolls.iter().map(|r| r.value).collect();
+    rolled.sort_by(f64::total_cmp);
+    assert_eq!(rolled, (1..=20).map(f64::from).collect::<Vec<_>>());
+
+    let output = run("3d{1,2}u", HashMap::new(), Seed::from_u64(0));
+    assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::DrawMax(2)));
+}
+
+#[test]
+fn explosions_and_success_counts_are_written_once() {
+    for (input, span) in [("1d20!{3}!", (8, 9)), ("4d10>=8f1>9", (9, 11))] {
+        let err = compile(input).unwrap_err();
+        assert_eq!(err.kind, RogErr::OptionRepeated);
+        assert_eq!(err.span, Some(span));
+    }
+}
+
+#[test]
+fn aro_stops_when_faces_are_all_the_same() {
+    let output = run("2d{3,3}aro", HashMap::new(), Seed::from_u64(0));
+    assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::AroMax));
+}
+
+#[test]
+fn aro_stops_when_clamps_make_dice_the_same() {
+    for input in ["2d6min6aro", "2d6max1aro"] {
+        let output = run(input, HashMap::new(), Seed::from_u64(0));
+        assert_eq!(output.map_err(|e| e.kind).err(), Some(RogErr::AroMax));
+    }
+}
+
+#[test]
+fn ranges_too_large_to_count_are_refused() {
+    for input in [
+        "d[0..99999999999999999999]",
+        "d[-9999999999999999999..9999999999999999999]",
+    ] {
+        let output = run(input, HashMap::new(), Seed::from_u64(0));
+        assert!(matches!(
+            output.map_err(|e| e.kind),
+            Err(RogErr::RangeMax(..))
+        ));
+    }
+    // Ranges that start at the lowest `i64` still fit, options included.
+    for input in [
+        "d[-9223372036854775808..-4611686018427387904]!",
+        "d[-9223372036854775808..-4611686018427387904]r1",
+    ] {
+        assert!(run(input, HashMap::new(), Seed::from_u64(0)).is_ok());
+    }
+}
+
+fn syntax_hint(input: &str) -> String {
+    let err = compile(input).unwrap_err();
+    let diagnostic = Diagnostic::new(&err, input, Locale::PtBr);
+    diagnostic.labels[0].message.clone()
+}
+
+#[test]
+fn syntax_errors_say_what_was_expected() {
+    assert_eq!(
+        syntax_hint("1d"),
+        "esperado os lados do dado depois de `1d`"
+    );
+    assert_eq!(syntax_hint("d{1,"), "esperado uma face depois de `,`");
+    assert_eq!(syntax_hint("d[1.."), "esperado um número depois de `1..`");
+    assert_eq!(
+        syntax_hint("2d6cs"),
+        "esperado uma comparação depois de `2d6cs`"
+    );
+    assert!(syntax_hint("1+").starts_with("esperado um dado, uma carta, um número"));
+}